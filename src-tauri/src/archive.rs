@@ -0,0 +1,723 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::manifest;
+use crate::meta;
+use crate::names;
+use crate::scope::Scope;
+use crate::staging::{self, Staging};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const IMPORT_PROGRESS_EVENT: &str = "archive-import-progress";
+
+/// Longest chain of symlinks followed before a link is taken for a loop.
+const MAX_LINK_HOPS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    const EXTENSIONS: [(&'static str, ArchiveFormat); 5] = [
+        (".zip", ArchiveFormat::Zip),
+        (".tar.gz", ArchiveFormat::TarGz),
+        (".tgz", ArchiveFormat::TarGz),
+        (".tar.zst", ArchiveFormat::TarZst),
+        (".tzst", ArchiveFormat::TarZst),
+    ];
+
+    /// Detects the format from the file name.
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(ext, _)| name.ends_with(ext))
+            .map(|(_, format)| *format)
+    }
+
+    /// The file name without its archive extension, e.g. `app-1.2` for
+    /// `app-1.2.tar.gz`.
+    pub fn stem(path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        let lower = name.to_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(ext, _)| lower.ends_with(ext))
+            .map(|(ext, _)| name[..name.len() - ext.len()].to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub program: String,
+    pub version: String,
+    pub entry: String,
+    pub entries_done: usize,
+    /// Compressed bytes consumed so far, out of `archive_size`.
+    pub bytes_read: u64,
+    pub archive_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub program: String,
+    pub version: String,
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// The top-level folder that was stripped, if any.
+    pub stripped: Option<String>,
+    /// Symlinks that could not be created on this platform.
+    pub skipped_links: Vec<String>,
+    pub duration_ms: u128,
+}
+
+/// Turns an archive entry name into a relative path, refusing anything
+/// that could land outside the target directory.
+fn safe_relative(name: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(&name.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "archive entry {:?} points outside the version",
+                    name
+                )))
+            }
+        }
+    }
+    Ok(relative)
+}
+
+/// Whether the symlink at `link` resolves to a path inside the version.
+/// `links` maps every symlink of the version, relative to it, to its
+/// target, so a target leading through other links is followed the way
+/// the filesystem will follow it once they all exist.
+pub fn link_stays_inside(links: &HashMap<PathBuf, PathBuf>, link: &Path) -> bool {
+    let Some(target) = links.get(link) else {
+        return false;
+    };
+    let mut resolved = link.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut pending: VecDeque<Component> = target.components().collect();
+    let mut hops = 0;
+    while let Some(component) = pending.pop_front() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                if let Some(next) = links.get(&resolved) {
+                    hops += 1;
+                    if hops > MAX_LINK_HOPS {
+                        return false;
+                    }
+                    resolved.pop();
+                    for component in next.components().rev() {
+                        pending.push_front(component);
+                    }
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Whether any directory above `relative` is one of `links`.
+fn passes_through_link(links: &HashMap<PathBuf, PathBuf>, relative: &Path) -> bool {
+    relative
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| links.contains_key(ancestor))
+}
+
+enum Link {
+    Symbolic(PathBuf, PathBuf),
+    Hard(PathBuf, PathBuf),
+}
+
+/// Writes archive entries below `dest`. Links are only created once every
+/// regular entry has been written, hard links before symlinks, and no entry
+/// may lie below a symlink, so nothing can be written through one.
+struct Extractor {
+    dest: PathBuf,
+    files: usize,
+    bytes: u64,
+    links: Vec<Link>,
+    /// Every symlink entry, relative path to target.
+    symlinks: HashMap<PathBuf, PathBuf>,
+    skipped_links: Vec<String>,
+}
+
+impl Extractor {
+    fn new(dest: PathBuf) -> Extractor {
+        Extractor {
+            dest,
+            files: 0,
+            bytes: 0,
+            links: Vec::new(),
+            symlinks: HashMap::new(),
+            skipped_links: Vec::new(),
+        }
+    }
+
+    fn check_path(&self, relative: &Path) -> Result<()> {
+        if passes_through_link(&self.symlinks, relative) {
+            return Err(Error::InvalidArgument(format!(
+                "archive entry {:?} lies below a symlink",
+                relative
+            )));
+        }
+        Ok(())
+    }
+
+    fn dir(&self, relative: &Path) -> Result<()> {
+        self.check_path(relative)?;
+        fs::create_dir_all(self.dest.join(relative))?;
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        relative: &Path,
+        reader: &mut dyn Read,
+        mode: Option<u32>,
+        mtime: Option<i64>,
+    ) -> Result<()> {
+        self.check_path(relative)?;
+        let path = self.dest.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&path)?;
+        self.bytes += io::copy(reader, &mut file)?;
+        drop(file);
+        self.files += 1;
+
+        if let Some(mtime) = mtime {
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(mtime, 0))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            // Setuid, setgid and sticky bits are not restored.
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        Ok(())
+    }
+
+    fn symlink(&mut self, relative: PathBuf, target: PathBuf) -> Result<()> {
+        self.check_path(&relative)?;
+        self.symlinks.insert(relative.clone(), target.clone());
+        self.links.push(Link::Symbolic(relative, target));
+        Ok(())
+    }
+
+    fn hardlink(&mut self, relative: PathBuf, target: &str) -> Result<()> {
+        let target = safe_relative(target)?;
+        self.check_path(&relative)?;
+        self.links.push(Link::Hard(relative, target));
+        Ok(())
+    }
+
+    /// Checks the symlinks again for content that is installed from `top`,
+    /// the stripped top-level folder: a link may stay inside the archive
+    /// yet leave that folder, such as `top/l -> ..`.
+    fn check_links_within(&self, top: &Path) -> Result<()> {
+        let links: HashMap<PathBuf, PathBuf> = self
+            .symlinks
+            .iter()
+            .filter_map(|(link, target)| {
+                Some((link.strip_prefix(top).ok()?.to_path_buf(), target.clone()))
+            })
+            .collect();
+        match links.keys().find(|link| !link_stays_inside(&links, link)) {
+            Some(link) => Err(Error::InvalidArgument(format!(
+                "link {:?} points outside the version",
+                top.join(link)
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Creates the deferred links. Hard links are materialized as copies.
+    /// Every link is checked against all symlinks of the archive first, as
+    /// a later entry may change where an earlier link leads.
+    fn finish(&mut self) -> Result<()> {
+        for link in &self.links {
+            let (relative, escapes) = match link {
+                Link::Symbolic(relative, _) => {
+                    (relative, !link_stays_inside(&self.symlinks, relative))
+                }
+                Link::Hard(relative, target) => (
+                    relative,
+                    passes_through_link(&self.symlinks, relative)
+                        || self.symlinks.contains_key(target)
+                        || passes_through_link(&self.symlinks, target),
+                ),
+            };
+            if escapes {
+                return Err(Error::InvalidArgument(format!(
+                    "link {:?} points outside the version",
+                    relative
+                )));
+            }
+        }
+
+        let mut links = std::mem::take(&mut self.links);
+        links.sort_by_key(|link| matches!(link, Link::Symbolic(..)));
+        for link in links {
+            match link {
+                Link::Hard(relative, target) => {
+                    let path = self.dest.join(&relative);
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    self.bytes += fs::copy(self.dest.join(target), path)?;
+                    self.files += 1;
+                }
+                #[cfg(unix)]
+                Link::Symbolic(relative, target) => {
+                    let path = self.dest.join(&relative);
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    std::os::unix::fs::symlink(target, path)?;
+                }
+                #[cfg(not(unix))]
+                Link::Symbolic(relative, _) => {
+                    self.skipped_links
+                        .push(relative.to_string_lossy().into_owned());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts the bytes pulled through a reader that is owned by a decoder.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Days since the unix epoch of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of `days_from_civil`: `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Zip timestamps carry no time zone; they are taken as UTC.
+fn zip_mtime(dt: zip::DateTime) -> i64 {
+    days_from_civil(dt.year().into(), dt.month().into(), dt.day().into()) * 86_400
+        + i64::from(dt.hour()) * 3600
+        + i64::from(dt.minute()) * 60
+        + i64::from(dt.second())
+}
+
+/// Unix seconds as a zip timestamp. Zip cannot represent dates outside
+/// 1980-2107, which fall back to the format's default.
+pub fn zip_datetime(secs: i64) -> zip::DateTime {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let time = secs.rem_euclid(86_400);
+    let parts = (
+        u16::try_from(year),
+        u8::try_from(month),
+        u8::try_from(day),
+        u8::try_from(time / 3600),
+        u8::try_from(time % 3600 / 60),
+        u8::try_from(time % 60),
+    );
+    match parts {
+        (Ok(y), Ok(mo), Ok(d), Ok(h), Ok(mi), Ok(s)) => {
+            zip::DateTime::from_date_and_time(y, mo, d, h, mi, s).unwrap_or_default()
+        }
+        _ => zip::DateTime::default(),
+    }
+}
+
+fn extract_zip<F>(archive: &Path, out: &mut Extractor, mut on_entry: F) -> Result<()>
+where
+    F: FnMut(&str, u64),
+{
+    let mut zip =
+        zip::ZipArchive::new(BufReader::new(File::open(archive)?)).map_err(io::Error::from)?;
+    let mut bytes_read = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(io::Error::from)?;
+        let name = entry.name().to_string();
+        let relative = safe_relative(&name)?;
+        bytes_read += entry.compressed_size();
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        if entry.is_dir() {
+            out.dir(&relative)?;
+        } else if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            out.symlink(relative, PathBuf::from(target))?;
+        } else {
+            let mode = entry.unix_mode();
+            let mtime = entry.last_modified().map(zip_mtime);
+            out.file(&relative, &mut entry, mode, mtime)?;
+        }
+        on_entry(&name, bytes_read);
+    }
+    Ok(())
+}
+
+fn extract_tar<F>(
+    archive: &Path,
+    format: ArchiveFormat,
+    out: &mut Extractor,
+    mut on_entry: F,
+) -> Result<()>
+where
+    F: FnMut(&str, u64),
+{
+    let count = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: BufReader::new(File::open(archive)?),
+        count: count.clone(),
+    };
+    let decoder: Box<dyn Read> = match format {
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(reader)?),
+        _ => Box::new(flate2::read::GzDecoder::new(reader)),
+    };
+    let mut tar = tar::Archive::new(decoder);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let relative = safe_relative(&name)?;
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let header = entry.header();
+        let (mode, mtime) = (header.mode().ok(), header.mtime().ok());
+        match header.entry_type() {
+            tar::EntryType::Directory => out.dir(&relative)?,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mtime = mtime.and_then(|t| i64::try_from(t).ok());
+                out.file(&relative, &mut entry, mode, mtime)?
+            }
+            tar::EntryType::Symlink => {
+                let target = entry.link_name()?.unwrap_or_default().into_owned();
+                out.symlink(relative, target)?
+            }
+            tar::EntryType::Link => {
+                let target = entry.link_name()?.unwrap_or_default();
+                out.hardlink(relative, &target.to_string_lossy())?
+            }
+            // Devices, fifos and metadata-only headers have no place in a build.
+            _ => {}
+        }
+        on_entry(&name, count.get());
+    }
+    Ok(())
+}
+
+/// Prepares the manifest of a new version. Content that carries one, such
+/// as archives written by `export` or copied versions, must match it;
+/// permissions alone may differ, e.g. on Windows. Other content gets a
+/// fresh manifest. Version metadata such as seals is local state and is
+/// not carried over.
+pub fn check_manifest(content: &Path, program: &str, version: &str) -> Result<()> {
+    match fs::remove_file(meta::meta_path(content)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let report = match manifest::verify(content) {
+        Ok(report) => report,
+        Err(Error::NotFound(_)) => {
+            manifest::generate(content, program, version)?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let modes_only = report.added.is_empty()
+        && report.removed.is_empty()
+        && report.unreadable.is_empty()
+        && report
+            .modified
+            .iter()
+            .all(|m| m.expected.size == m.actual.size && m.expected.sha256 == m.actual.sha256);
+    if !modes_only {
+        return Err(Error::InvalidArgument(format!(
+            "content does not match its manifest ({} added, {} removed, {} modified)",
+            report.added.len(),
+            report.removed.len(),
+            report.modified.len() + report.unreadable.len()
+        )));
+    }
+    if report.intact {
+        manifest::relabel(content, program, version)
+    } else {
+        manifest::generate(content, program, version).map(|_| ())
+    }
+}
+
+/// With `strip_top_level`, a lone top-level folder becomes the version
+/// root. Returns the directory to install and the stripped folder name.
+fn content_root(extracted: &Path, strip_top_level: bool) -> Result<(PathBuf, Option<String>)> {
+    if strip_top_level {
+        let entries = fs::read_dir(extracted)?.collect::<io::Result<Vec<_>>>()?;
+        if let [only] = entries.as_slice() {
+            if only.file_type()?.is_dir() {
+                let name = only.file_name().to_string_lossy().into_owned();
+                return Ok((only.path(), Some(name)));
+            }
+        }
+    }
+    Ok((extracted.to_path_buf(), None))
+}
+
+/// Extracts `archive` into `<root>/<program>/<version>`. Everything is
+/// unpacked and checked against or hashed into a manifest in a staging
+/// directory first; the version only appears, through a single rename, once
+/// that has succeeded.
+pub fn import<F>(
+    root: &Path,
+    scope: &Scope,
+    archive: &Path,
+    program: &str,
+    version: &str,
+    strip_top_level: bool,
+    mut on_progress: F,
+) -> Result<ImportReport>
+where
+    F: FnMut(&ImportProgress),
+{
+    let started = Instant::now();
+    let format = ArchiveFormat::from_path(archive).ok_or_else(|| {
+        Error::InvalidArgument(format!("{} is not a supported archive", archive.display()))
+    })?;
+    staging::ensure_absent(root, program, version)?;
+
+    let archive_size = fs::metadata(archive)?.len();
+    let staging = Staging::new(root, "import")?;
+    let extracted = staging.content();
+    fs::create_dir_all(&extracted)?;
+
+    let mut out = Extractor::new(extracted.clone());
+    let mut entries_done = 0;
+    let on_entry = |entry: &str, bytes_read: u64| {
+        entries_done += 1;
+        on_progress(&ImportProgress {
+            program: program.to_string(),
+            version: version.to_string(),
+            entry: entry.to_string(),
+            entries_done,
+            bytes_read,
+            archive_size,
+        });
+    };
+    match format {
+        ArchiveFormat::Zip => extract_zip(archive, &mut out, on_entry)?,
+        _ => extract_tar(archive, format, &mut out, on_entry)?,
+    }
+    out.finish()?;
+
+    let (content, stripped) = content_root(&extracted, strip_top_level)?;
+    if let Some(top) = &stripped {
+        out.check_links_within(Path::new(top))?;
+    }
+    check_manifest(&content, program, version)?;
+    let target = staging::install(root, scope, &content, program, version)?;
+    Ok(ImportReport {
+        program: program.to_string(),
+        version: version.to_string(),
+        path: target.to_string_lossy().into_owned(),
+        files: out.files,
+        bytes: out.bytes,
+        stripped,
+        skipped_links: out.skipped_links,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[tauri::command(async)]
+pub fn import_archive(
+    app: AppHandle,
+    archive: String,
+    program: String,
+    version: String,
+    strip_top_level: Option<bool>,
+) -> Result<ImportReport> {
+    let config = config::load(&app)?;
+    let root = config.local_root();
+    fs::create_dir_all(&root)?;
+    let scope = Scope::from_config(&config)?;
+    import(
+        &root,
+        &scope,
+        Path::new(&archive),
+        &program,
+        &names::normalize(&version)?,
+        strip_top_level.unwrap_or(false),
+        |progress| {
+            let _ = app.emit_all(IMPORT_PROGRESS_EVENT, progress);
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(pairs: &[(&str, &str)]) -> HashMap<PathBuf, PathBuf> {
+        pairs
+            .iter()
+            .map(|(link, target)| (PathBuf::from(link), PathBuf::from(target)))
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pm-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn safe_relative_keeps_names_inside() {
+        assert_eq!(safe_relative("a/./b").unwrap(), PathBuf::from("a/b"));
+        assert_eq!(safe_relative("a\\b").unwrap(), PathBuf::from("a/b"));
+        assert!(safe_relative("../a").is_err());
+        assert!(safe_relative("a/../../b").is_err());
+        assert!(safe_relative("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn links_inside_the_version_are_accepted() {
+        let links = links(&[("a/up", ".."), ("a/b/sib", "../c"), ("top", "a/b")]);
+        assert!(link_stays_inside(&links, Path::new("a/up")));
+        assert!(link_stays_inside(&links, Path::new("a/b/sib")));
+        assert!(link_stays_inside(&links, Path::new("top")));
+    }
+
+    #[test]
+    fn links_leaving_the_version_are_refused() {
+        let links = links(&[("a", "../x"), ("b/c", "../../x"), ("abs", "/etc")]);
+        assert!(!link_stays_inside(&links, Path::new("a")));
+        assert!(!link_stays_inside(&links, Path::new("b/c")));
+        assert!(!link_stays_inside(&links, Path::new("abs")));
+    }
+
+    #[test]
+    fn chained_links_are_resolved_through_each_other() {
+        // Lexically `a/l/..` is `a`, but `a/l` already leads to the root.
+        let links = links(&[("a/l", ".."), ("m", "a/l/.."), ("m2", "m/..")]);
+        assert!(link_stays_inside(&links, Path::new("a/l")));
+        assert!(!link_stays_inside(&links, Path::new("m")));
+        assert!(!link_stays_inside(&links, Path::new("m2")));
+    }
+
+    #[test]
+    fn link_loops_are_refused() {
+        let links = links(&[("a", "b"), ("b", "a")]);
+        assert!(!link_stays_inside(&links, Path::new("a")));
+    }
+
+    #[test]
+    fn extractor_refuses_link_chains_and_entries_below_links() {
+        let dest = temp_dir("chain");
+        let mut out = Extractor::new(dest.join("content"));
+        out.symlink("a/l".into(), "..".into()).unwrap();
+        out.symlink("m".into(), "a/l/..".into()).unwrap();
+        out.hardlink("m/x".into(), "a/f").unwrap_err();
+        assert!(out.finish().is_err());
+        assert!(!dest.join("x").exists());
+
+        let mut out = Extractor::new(dest.join("below"));
+        out.symlink("l".into(), ".".into()).unwrap();
+        assert!(out
+            .file(Path::new("l/f"), &mut io::empty(), None, None)
+            .is_err());
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn links_leaving_a_stripped_folder_are_refused() {
+        let dest = temp_dir("strip");
+        let mut out = Extractor::new(dest.clone());
+        out.file(Path::new("top/f"), &mut io::empty(), None, None)
+            .unwrap();
+        out.symlink("top/inside".into(), "f".into()).unwrap();
+        out.finish().unwrap();
+        out.check_links_within(Path::new("top")).unwrap();
+
+        for (link, target) in [("top/l", ".."), ("top/x", "../top/..")] {
+            let mut out = Extractor::new(dest.join(link.replace('/', "-")));
+            out.symlink(link.into(), target.into()).unwrap();
+            out.finish().unwrap();
+            assert!(
+                out.check_links_within(Path::new("top")).is_err(),
+                "{}",
+                link
+            );
+        }
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extractor_drops_setuid_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let dest = temp_dir("mode");
+        let mut out = Extractor::new(dest.clone());
+        out.file(Path::new("f"), &mut io::empty(), Some(0o4755), None)
+            .unwrap();
+        let mode = fs::metadata(dest.join("f")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        fs::remove_dir_all(&dest).unwrap();
+    }
+}
@@ -0,0 +1,704 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil::{self, WalkedFile};
+use crate::seal;
+use crate::sync::{self, FileStatus, SyncFailure, SyncOptions, SyncProgress, SyncSummary};
+use crate::trash;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::AppHandle;
+
+const STATE_DIR: &str = "sync-state";
+
+/// A run that would delete more files than this, on either side, stops and
+/// asks for `SyncOptions::confirm_deletes`. Far more deletions than usual
+/// are more likely a half-copied or reformatted drive than intent.
+pub const MAX_UNCONFIRMED_DELETES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileState {
+    pub size: u64,
+    /// Nanoseconds since the unix epoch.
+    pub mtime: i64,
+}
+
+impl FileState {
+    fn of(meta: &fs::Metadata) -> FileState {
+        FileState {
+            size: meta.len(),
+            mtime: fsutil::mtime_nanos(meta),
+        }
+    }
+}
+
+/// What each side looked like right after the last successful sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseRecord {
+    pub local: FileState,
+    pub mirror: FileState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub path: String,
+    /// `None` when the file was deleted on that side.
+    pub local: Option<FileState>,
+    pub mirror: Option<FileState>,
+    pub detected_at: u64,
+}
+
+/// Which copy of a conflicted file survives.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Resolution {
+    #[serde(rename = "keepLocal")]
+    Local,
+    #[serde(rename = "keepMirror")]
+    Mirror,
+    /// Keep local under the original name and the mirror's copy next to it.
+    #[serde(rename = "keepBoth")]
+    Both,
+}
+
+/// Persisted per local/mirror pair in `<appDataDir>/sync-state/`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub local_root: String,
+    pub mirror_root: String,
+    pub files: BTreeMap<String, BaseRecord>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn state_path(app: &AppHandle, local: &Path, mirror: &Path) -> Result<PathBuf> {
+    let key = format!("{}\0{}", local.display(), mirror.display());
+    let digest = fsutil::sha256_bytes(key.as_bytes());
+    Ok(config::app_data_dir(app)?
+        .join(STATE_DIR)
+        .join(format!("{}.json", &digest[..16])))
+}
+
+impl SyncState {
+    pub fn load(path: &Path, local: &Path, mirror: &Path) -> Result<SyncState> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SyncState {
+                local_root: local.to_string_lossy().into_owned(),
+                mirror_root: mirror.to_string_lossy().into_owned(),
+                ..Default::default()
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(temp, path)?;
+        Ok(())
+    }
+}
+
+fn side_path(root: &Path, key: &str) -> PathBuf {
+    key.split('/')
+        .fold(root.to_path_buf(), |p, part| p.join(part))
+}
+
+fn by_key(files: Vec<WalkedFile>, options: &SyncOptions) -> BTreeMap<String, fs::Metadata> {
+    files
+        .into_iter()
+        .filter(|f| sync::in_programs(&f.relative, &options.programs))
+        .map(|f| (fsutil::relative_key(&f.relative), f.metadata))
+        .collect()
+}
+
+fn current_state(path: &Path) -> Result<Option<FileState>> {
+    match fs::metadata(path) {
+        Ok(meta) => Ok(Some(FileState::of(&meta))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Records the post-sync state of both sides, or forgets the file when it
+/// no longer exists on either.
+fn record(state: &mut SyncState, key: &str, local: &Path, mirror: &Path) -> Result<()> {
+    match (
+        current_state(&side_path(local, key))?,
+        current_state(&side_path(mirror, key))?,
+    ) {
+        (Some(l), Some(m)) => {
+            state.files.insert(
+                key.to_string(),
+                BaseRecord {
+                    local: l,
+                    mirror: m,
+                },
+            );
+        }
+        _ => {
+            state.files.remove(key);
+        }
+    }
+    Ok(())
+}
+
+fn same_content(a: &Path, b: &Path, a_meta: &fs::Metadata, b_meta: &fs::Metadata) -> Result<bool> {
+    if a_meta.len() != b_meta.len() {
+        return Ok(false);
+    }
+    Ok(fsutil::sha256_file(a)? == fsutil::sha256_file(b)?)
+}
+
+enum Action {
+    None,
+    Push,
+    Pull,
+    DeleteMirror,
+    DeleteLocal,
+    Conflict,
+}
+
+fn plan(
+    key: &str,
+    local: Option<&fs::Metadata>,
+    mirror: Option<&fs::Metadata>,
+    base: Option<&BaseRecord>,
+    roots: (&Path, &Path),
+    propagate_deletes: bool,
+) -> Result<Action> {
+    let local_changed = local.map(FileState::of) != base.map(|b| b.local);
+    let mirror_changed = mirror.map(FileState::of) != base.map(|b| b.mirror);
+    Ok(match (local_changed, mirror_changed) {
+        (false, false) => Action::None,
+        (true, false) => match (local, base) {
+            (Some(_), _) => Action::Push,
+            (None, Some(_)) if propagate_deletes => Action::DeleteMirror,
+            (None, _) => Action::Pull,
+        },
+        (false, true) => match (mirror, base) {
+            (Some(_), _) => Action::Pull,
+            (None, Some(_)) if propagate_deletes => Action::DeleteLocal,
+            (None, _) => Action::Push,
+        },
+        (true, true) => match (local, mirror) {
+            (None, None) => Action::None,
+            (Some(l), Some(m))
+                if same_content(&side_path(roots.0, key), &side_path(roots.1, key), l, m)? =>
+            {
+                Action::None
+            }
+            _ => Action::Conflict,
+        },
+    })
+}
+
+/// Two-way sync between `local` and `mirror` against the recorded state of
+/// the previous run. Files changed on only one side are copied to the
+/// other; files changed differently on both sides are recorded as
+/// conflicts and left untouched. Deletions are only propagated when
+/// `options.propagate_deletes` is set; otherwise the surviving copy is
+/// restored to the side it was deleted from. Files deleted locally go to the
+/// trash.
+///
+/// Nothing is touched when one side is empty although the last run
+/// recorded files, which is what an unmounted or wiped drive looks like,
+/// or when more than `MAX_UNCONFIRMED_DELETES` files would be deleted
+/// without `options.confirm_deletes`.
+pub fn sync_two_way<F>(
+    local: &Path,
+    mirror: &Path,
+    state: &mut SyncState,
+    options: &SyncOptions,
+    mut on_progress: F,
+) -> Result<SyncSummary>
+where
+    F: FnMut(&SyncProgress),
+{
+    let started = Instant::now();
+    let local_files = by_key(fsutil::walk_files(local, true)?, options);
+    let mirror_files = by_key(fsutil::walk_files(mirror, true)?, options);
+    let keys: BTreeSet<String> = local_files
+        .keys()
+        .chain(mirror_files.keys())
+        .chain(
+            state
+                .files
+                .keys()
+                .filter(|k| sync::in_programs(Path::new(k.as_str()), &options.programs)),
+        )
+        .cloned()
+        .collect();
+
+    let recorded = state
+        .files
+        .keys()
+        .filter(|k| sync::in_programs(Path::new(k.as_str()), &options.programs))
+        .count();
+    if recorded > 0 && mirror_files.is_empty() {
+        return Err(Error::MirrorUnavailable(format!(
+            "{} is empty although the last sync recorded {} files; is the right drive mounted?",
+            mirror.display(),
+            recorded
+        )));
+    }
+    if recorded > 0 && local_files.is_empty() {
+        return Err(Error::Config(format!(
+            "{} is empty although the last sync recorded {} files",
+            local.display(),
+            recorded
+        )));
+    }
+
+    let pending: BTreeSet<String> = state.conflicts.iter().map(|c| c.path.clone()).collect();
+    // Planned up front so the deletions can be counted before any happen.
+    let mut actions = Vec::with_capacity(keys.len());
+    for key in &keys {
+        let action = if pending.contains(key) {
+            None
+        } else {
+            Some(plan(
+                key,
+                local_files.get(key),
+                mirror_files.get(key),
+                state.files.get(key),
+                (local, mirror),
+                options.propagate_deletes,
+            ))
+        };
+        actions.push(action);
+    }
+    let deletes = actions
+        .iter()
+        .filter(|a| matches!(a, Some(Ok(Action::DeleteLocal | Action::DeleteMirror))))
+        .count();
+    if deletes > MAX_UNCONFIRMED_DELETES && !options.confirm_deletes {
+        return Err(Error::NeedsConfirmation(format!(
+            "two-way sync would delete {} files",
+            deletes
+        )));
+    }
+
+    let mut summary = SyncSummary {
+        files_total: keys.len(),
+        ..Default::default()
+    };
+    let mut pushable = seal::PushCheck::new(local);
+
+    for (i, (key, action)) in keys.iter().zip(actions).enumerate() {
+        let local_meta = local_files.get(key);
+        let mirror_meta = mirror_files.get(key);
+        let mut progress = SyncProgress {
+            path: key.clone(),
+            status: FileStatus::Unchanged,
+            bytes_copied: 0,
+            file_size: local_meta.or(mirror_meta).map(|m| m.len()).unwrap_or(0),
+            files_done: i + 1,
+            files_total: keys.len(),
+            error: None,
+        };
+
+        // Unresolved conflicts stay as they are until the user decides.
+        let Some(action) = action else {
+            summary.conflicts += 1;
+            progress.status = FileStatus::Conflict;
+            on_progress(&progress);
+            continue;
+        };
+
+        let local_path = side_path(local, key);
+        let mirror_path = side_path(mirror, key);
+        let result = action.and_then(|action| {
+            let status = match action {
+                Action::None => FileStatus::Unchanged,
+                Action::Push => {
+                    pushable.ensure_pushable(Path::new(key))?;
+                    let existed = mirror_meta.is_some();
+                    fsutil::copy_file_atomic(&local_path, &mirror_path, |_| {})?;
+                    if existed {
+                        FileStatus::Updated
+                    } else {
+                        FileStatus::Added
+                    }
+                }
+                Action::Pull => {
+                    seal::ensure_relative_unsealed(local, Path::new(key))?;
+                    fsutil::copy_file_atomic(&mirror_path, &local_path, |_| {})?;
+                    FileStatus::Pulled
+                }
+                Action::DeleteMirror => {
+                    seal::ensure_relative_unsealed(mirror, Path::new(key))?;
+                    fs::remove_file(&mirror_path)?;
+                    FileStatus::Deleted
+                }
+                Action::DeleteLocal => {
+                    seal::ensure_relative_unsealed(local, Path::new(key))?;
+                    trash::move_file_to_trash(local, key)?;
+                    FileStatus::Deleted
+                }
+                Action::Conflict => {
+                    state.conflicts.push(Conflict {
+                        path: key.clone(),
+                        local: local_meta.map(FileState::of),
+                        mirror: mirror_meta.map(FileState::of),
+                        detected_at: fsutil::now_secs(),
+                    });
+                    return Ok(FileStatus::Conflict);
+                }
+            };
+            record(state, key, local, mirror)?;
+            Ok(status)
+        });
+
+        match result {
+            Ok(status) => {
+                progress.status = status;
+                match status {
+                    FileStatus::Added => summary.added += 1,
+                    FileStatus::Updated => summary.updated += 1,
+                    FileStatus::Pulled => summary.pulled += 1,
+                    FileStatus::Deleted => summary.deleted += 1,
+                    FileStatus::Conflict => summary.conflicts += 1,
+                    _ => summary.unchanged += 1,
+                }
+                let copied_from = match status {
+                    FileStatus::Added | FileStatus::Updated => local_meta,
+                    FileStatus::Pulled => mirror_meta,
+                    _ => None,
+                };
+                summary.bytes_copied += copied_from.map(|m| m.len()).unwrap_or(0);
+            }
+            Err(e) => {
+                summary.failed += 1;
+                progress.status = FileStatus::Failed;
+                progress.error = Some(e.to_string());
+                summary.failures.push(SyncFailure {
+                    path: key.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+        on_progress(&progress);
+    }
+
+    summary.duration_ms = started.elapsed().as_millis();
+    Ok(summary)
+}
+
+/// Name for the mirror's copy when both versions of a conflict are kept,
+/// e.g. `app.json` -> `app (mirror 1718000000).json`.
+fn keep_both_key(key: &str, at: u64) -> String {
+    let (dir, name) = match key.rfind('/') {
+        Some(i) => (&key[..=i], &key[i + 1..]),
+        None => ("", key),
+    };
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    };
+    format!("{}{} (mirror {}){}", dir, stem, at, ext)
+}
+
+pub fn resolve(
+    local: &Path,
+    mirror: &Path,
+    state: &mut SyncState,
+    key: &str,
+    resolution: Resolution,
+) -> Result<()> {
+    let position = state
+        .conflicts
+        .iter()
+        .position(|c| c.path == key)
+        .ok_or_else(|| Error::NotFound(format!("sync conflict {}", key)))?;
+    let local_path = side_path(local, key);
+    let mirror_path = side_path(mirror, key);
+
+    // Every resolution except keeping the local copy writes to local.
+    if !matches!(resolution, Resolution::Local) {
+        seal::ensure_relative_unsealed(local, Path::new(key))?;
+    }
+    match resolution {
+        Resolution::Local => {
+            if local_path.exists() {
+                fsutil::copy_file_atomic(&local_path, &mirror_path, |_| {})?;
+            } else if mirror_path.exists() {
+                fs::remove_file(&mirror_path)?;
+            }
+        }
+        // As in a sync, a local copy that gives way goes to the trash.
+        Resolution::Mirror => {
+            if mirror_path.exists() {
+                fsutil::copy_file_atomic(&mirror_path, &local_path, |_| {})?;
+            } else if local_path.exists() {
+                trash::move_file_to_trash(local, key)?;
+            }
+        }
+        Resolution::Both => {
+            if mirror_path.exists() && local_path.exists() {
+                let renamed = keep_both_key(key, fsutil::now_secs());
+                fsutil::copy_file_atomic(&mirror_path, &side_path(local, &renamed), |_| {})?;
+                fsutil::copy_file_atomic(&mirror_path, &side_path(mirror, &renamed), |_| {})?;
+                record(state, &renamed, local, mirror)?;
+                fsutil::copy_file_atomic(&local_path, &mirror_path, |_| {})?;
+            } else if local_path.exists() {
+                fsutil::copy_file_atomic(&local_path, &mirror_path, |_| {})?;
+            } else if mirror_path.exists() {
+                fsutil::copy_file_atomic(&mirror_path, &local_path, |_| {})?;
+            }
+        }
+    }
+
+    record(state, key, local, mirror)?;
+    state.conflicts.remove(position);
+    Ok(())
+}
+
+fn load_state(app: &AppHandle) -> Result<(PathBuf, PathBuf, PathBuf, SyncState)> {
+    let config = config::load(app)?;
+    let (local, mirror) = sync::mirror_roots(&config)?;
+    let path = state_path(app, &local, &mirror)?;
+    let state = SyncState::load(&path, &local, &mirror)?;
+    Ok((local, mirror, path, state))
+}
+
+/// Carries the recorded state over to a program or version that was renamed
+/// on both sides, so the next two-way sync does not take the rename for a
+/// deletion and a new item.
+pub fn rename_keys(
+    app: &AppHandle,
+    local: &Path,
+    mirror: &Path,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let path = state_path(app, local, mirror)?;
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut state = SyncState::load(&path, local, mirror)?;
+    state.files = std::mem::take(&mut state.files)
+        .into_iter()
+        .map(|(key, record)| (fsutil::rekey(&key, from, to).unwrap_or(key), record))
+        .collect();
+    for conflict in &mut state.conflicts {
+        if let Some(key) = fsutil::rekey(&conflict.path, from, to) {
+            conflict.path = key;
+        }
+    }
+    state.save(&path)
+}
+
+/// Runs a two-way sync and persists the resulting state. Used by
+/// `sync_mirror` when `mode` is `twoWay`.
+pub fn run(
+    app: &AppHandle,
+    options: &SyncOptions,
+    on_progress: impl FnMut(&SyncProgress),
+) -> Result<SyncSummary> {
+    let (local, mirror, path, mut state) = load_state(app)?;
+    let result = sync_two_way(&local, &mirror, &mut state, options, on_progress);
+    // Save even after a failure so files already synced are not re-examined
+    // as conflicts on the next run.
+    state.save(&path)?;
+    result
+}
+
+#[tauri::command]
+pub fn list_sync_conflicts(app: AppHandle) -> Result<Vec<Conflict>> {
+    let (_, _, _, state) = load_state(&app)?;
+    Ok(state.conflicts)
+}
+
+#[tauri::command(async)]
+pub fn resolve_sync_conflict(app: AppHandle, path: String, resolution: Resolution) -> Result<()> {
+    let _guard = sync::RunGuard::acquire("a mirror sync")?;
+    let (local, mirror, state_file, mut state) = load_state(&app)?;
+    resolve(&local, &mirror, &mut state, &path, resolution)?;
+    state.save(&state_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    struct Pair {
+        _dir: TempDir,
+        local: PathBuf,
+        mirror: PathBuf,
+        state: SyncState,
+    }
+
+    impl Pair {
+        /// Both sides holding `files`, synced once so the state records them.
+        fn synced(files: &[&str]) -> Pair {
+            let dir = TempDir::new("bisync");
+            let (local, mirror) = (dir.join("local"), dir.join("mirror"));
+            fs::create_dir_all(&mirror).unwrap();
+            for file in files {
+                testutil::write(&local.join(file), file);
+            }
+            let mut pair = Pair {
+                _dir: dir,
+                local,
+                mirror,
+                state: SyncState::default(),
+            };
+            let summary = pair.sync(&options(true)).unwrap();
+            assert_eq!(summary.added, files.len());
+            pair
+        }
+
+        fn sync(&mut self, options: &SyncOptions) -> Result<SyncSummary> {
+            sync_two_way(&self.local, &self.mirror, &mut self.state, options, |_| {})
+        }
+
+        fn resolve(&mut self, key: &str, resolution: Resolution) -> Result<()> {
+            resolve(&self.local, &self.mirror, &mut self.state, key, resolution)
+        }
+    }
+
+    fn options(propagate_deletes: bool) -> SyncOptions {
+        SyncOptions {
+            propagate_deletes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn local_deletions_on_the_mirror_side_go_to_the_trash() {
+        let mut pair = Pair::synced(&["App/1.0/a.txt", "App/1.0/b.txt"]);
+        fs::remove_file(pair.mirror.join("App/1.0/a.txt")).unwrap();
+        fs::remove_file(pair.local.join("App/1.0/b.txt")).unwrap();
+
+        let summary = pair.sync(&options(true)).unwrap();
+        assert_eq!(summary.deleted, 2);
+        assert!(!pair.local.join("App/1.0/a.txt").exists());
+        assert!(!pair.mirror.join("App/1.0/b.txt").exists());
+
+        let trashed = trash::list(&pair.local).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].file.as_deref(), Some("a.txt"));
+        let scope = crate::scope::Scope::from_config(&config::AppConfig {
+            local_path: pair.local.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        trash::restore(&pair.local, &scope, &trashed[0].id).unwrap();
+        assert_eq!(
+            testutil::read(&pair.local.join("App/1.0/a.txt")).as_deref(),
+            Some("App/1.0/a.txt")
+        );
+    }
+
+    #[test]
+    fn deletions_are_undone_unless_propagated() {
+        let mut pair = Pair::synced(&["App/1.0/a.txt", "App/1.0/b.txt"]);
+        fs::remove_file(pair.mirror.join("App/1.0/a.txt")).unwrap();
+        let summary = pair.sync(&options(false)).unwrap();
+        assert_eq!(summary.added, 1);
+        assert!(pair.mirror.join("App/1.0/a.txt").exists());
+        assert!(pair.local.join("App/1.0/a.txt").exists());
+    }
+
+    #[test]
+    fn changes_on_both_sides_become_conflicts() {
+        let mut pair = Pair::synced(&["App/1.0/a.txt", "App/1.0/b.txt"]);
+        testutil::write(&pair.local.join("App/1.0/a.txt"), "local edit");
+        testutil::write(&pair.mirror.join("App/1.0/a.txt"), "mirror edit!");
+        testutil::write(&pair.local.join("App/1.0/b.txt"), "local edit");
+        fs::remove_file(pair.mirror.join("App/1.0/b.txt")).unwrap();
+
+        let summary = pair.sync(&options(true)).unwrap();
+        assert_eq!(summary.conflicts, 2);
+        let read = |root: &Path, file: &str| testutil::read(&root.join("App/1.0").join(file));
+        assert_eq!(read(&pair.local, "a.txt").as_deref(), Some("local edit"));
+        assert_eq!(read(&pair.mirror, "a.txt").as_deref(), Some("mirror edit!"));
+
+        // Conflicts stay until they are resolved.
+        assert_eq!(pair.sync(&options(true)).unwrap().conflicts, 2);
+
+        pair.resolve("App/1.0/a.txt", Resolution::Both).unwrap();
+        assert_eq!(read(&pair.mirror, "a.txt").as_deref(), Some("local edit"));
+        let kept = fs::read_dir(pair.local.join("App/1.0"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .find(|name| name.starts_with("a (mirror "))
+            .unwrap();
+        assert_eq!(read(&pair.local, &kept).as_deref(), Some("mirror edit!"));
+        assert_eq!(read(&pair.mirror, &kept).as_deref(), Some("mirror edit!"));
+
+        // Keeping the mirror's deletion trashes the local copy.
+        pair.resolve("App/1.0/b.txt", Resolution::Mirror).unwrap();
+        assert!(read(&pair.local, "b.txt").is_none());
+        let trashed = trash::list(&pair.local).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].file.as_deref(), Some("b.txt"));
+
+        assert!(pair.state.conflicts.is_empty());
+        assert!(matches!(
+            pair.resolve("App/1.0/b.txt", Resolution::Local),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(pair.sync(&options(true)).unwrap().conflicts, 0);
+    }
+
+    #[test]
+    fn keeping_local_restores_a_file_deleted_on_the_mirror() {
+        let mut pair = Pair::synced(&["App/1.0/a.txt", "App/1.0/b.txt"]);
+        testutil::write(&pair.local.join("App/1.0/a.txt"), "local edit");
+        fs::remove_file(pair.mirror.join("App/1.0/a.txt")).unwrap();
+        assert_eq!(pair.sync(&options(true)).unwrap().conflicts, 1);
+
+        pair.resolve("App/1.0/a.txt", Resolution::Local).unwrap();
+        assert_eq!(
+            testutil::read(&pair.mirror.join("App/1.0/a.txt")).as_deref(),
+            Some("local edit")
+        );
+        assert!(trash::list(&pair.local).unwrap().is_empty());
+    }
+
+    #[test]
+    fn an_empty_side_is_refused() {
+        let mut pair = Pair::synced(&["App/1.0/a.txt"]);
+        fs::remove_dir_all(&pair.mirror).unwrap();
+        fs::create_dir_all(&pair.mirror).unwrap();
+        assert!(matches!(
+            pair.sync(&options(true)),
+            Err(Error::MirrorUnavailable(_))
+        ));
+        assert!(pair.local.join("App/1.0/a.txt").exists());
+    }
+
+    #[test]
+    fn mass_deletions_need_confirmation() {
+        let files: Vec<String> = (0..=MAX_UNCONFIRMED_DELETES)
+            .map(|i| format!("App/1.0/f{}.txt", i))
+            .collect();
+        let mut pair = Pair::synced(&files.iter().map(String::as_str).collect::<Vec<_>>());
+        testutil::write(&pair.mirror.join("App/2.0/keep.txt"), "keep");
+        fs::remove_dir_all(pair.mirror.join("App/1.0")).unwrap();
+
+        assert!(matches!(
+            pair.sync(&options(true)),
+            Err(Error::NeedsConfirmation(_))
+        ));
+        assert!(pair.local.join("App/1.0/f0.txt").exists());
+
+        let confirmed = SyncOptions {
+            confirm_deletes: true,
+            ..options(true)
+        };
+        assert_eq!(pair.sync(&confirmed).unwrap().deleted, files.len());
+        assert_eq!(trash::list(&pair.local).unwrap().len(), files.len());
+    }
+}
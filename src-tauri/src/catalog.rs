@@ -0,0 +1,257 @@
+use crate::config;
+use crate::error::Result;
+use crate::meta::{self, ParentVersion};
+use crate::scrub::{self, VersionIntegrity};
+use crate::versioning::{self, VersionScheme};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use tauri::{AppHandle, Manager};
+
+/// Emitted once per program as soon as all of its versions have been walked.
+pub const SCAN_PROGRAM_EVENT: &str = "catalog-scan-program";
+/// Emitted after the last program, with the number of programs found.
+pub const SCAN_FINISHED_EVENT: &str = "catalog-scan-finished";
+
+static NEXT_SCAN_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileNode {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<FileNode>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramVersion {
+    pub version: String,
+    pub path: String,
+    /// Only filled in for deep scans; the module view normally loads the
+    /// tree one level at a time through `list_directory`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<FileNode>>,
+    /// Health of the local and mirror copies as of the last scrub.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<VersionIntegrity>,
+    pub sealed: bool,
+    /// The version this one was cloned from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ParentVersion>,
+    /// How the name was understood as a version number; `None` if it was
+    /// not, which the UI flags.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<VersionScheme>,
+    pub prerelease: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Program {
+    pub name: String,
+    /// Oldest first, with names that are not version numbers at the end.
+    pub versions: Vec<ProgramVersion>,
+    /// The highest version number, pre-releases included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_stable: Option<String>,
+}
+
+impl Program {
+    /// Parses the version names, sorts them by version number and works
+    /// out the latest ones.
+    pub fn new(name: String, mut versions: Vec<ProgramVersion>) -> Program {
+        for version in &mut versions {
+            let parsed = versioning::parse(&version.version);
+            version.scheme = parsed.as_ref().map(|p| p.scheme);
+            version.prerelease = parsed.is_some_and(|p| p.is_prerelease());
+        }
+        versioning::sort_by_version(&mut versions, |v| &v.version);
+        let latest = |stable_only: bool| {
+            versions
+                .iter()
+                .rev()
+                .find(|v| v.scheme.is_some() && !(stable_only && v.prerelease))
+                .map(|v| v.version.clone())
+        };
+        Program {
+            latest: latest(false),
+            latest_stable: latest(true),
+            name,
+            versions,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanProgramPayload<'a> {
+    scan_id: u64,
+    program: &'a Program,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanFinishedPayload {
+    scan_id: u64,
+    program_count: usize,
+}
+
+/// Internal bookkeeping directories (trash, staging, ...) start with a dot
+/// and are never part of the catalog.
+pub fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Per-version bookkeeping (manifest, metadata) lives in this directory
+/// inside each version and is hidden from the module view.
+pub const VERSION_META_DIR: &str = ".pm";
+
+/// Directories first, then case-insensitive by name, matching the order the
+/// module view has always used.
+pub fn compare_entries(a_dir: bool, a_name: &str, b_dir: bool, b_name: &str) -> Ordering {
+    b_dir
+        .cmp(&a_dir)
+        .then_with(|| a_name.to_lowercase().cmp(&b_name.to_lowercase()))
+        .then_with(|| a_name.cmp(b_name))
+}
+
+fn compare_nodes(a: &FileNode, b: &FileNode) -> Ordering {
+    compare_entries(a.is_directory, &a.name, b.is_directory, &b.name)
+}
+
+/// Lists the visible subdirectories of `path` as `(name, path)` pairs.
+pub fn list_subdirs(path: &Path) -> Result<Vec<(String, String)>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_hidden(&name) || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        dirs.push((name, entry.path().to_string_lossy().into_owned()));
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Walks a directory tree. Unreadable subdirectories are reported as empty
+/// rather than failing the whole scan.
+pub fn read_tree(path: &Path) -> Vec<FileNode> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() != VERSION_META_DIR)
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            eprintln!("Error reading directory {:?}: {}", path, e);
+            return Vec::new();
+        }
+    };
+
+    let mut nodes: Vec<FileNode> = entries
+        .par_iter()
+        .map(|entry| {
+            let entry_path = entry.path();
+            let is_directory = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            FileNode {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path: entry_path.to_string_lossy().into_owned(),
+                is_directory,
+                children: is_directory.then(|| read_tree(&entry_path)),
+            }
+        })
+        .collect();
+    nodes.sort_by(compare_nodes);
+    nodes
+}
+
+fn scan_program(name: String, path: &Path, deep: bool) -> Result<Program> {
+    let versions = list_subdirs(path)?
+        .into_par_iter()
+        .map(|(version, version_path)| ProgramVersion {
+            modules: deep.then(|| read_tree(Path::new(&version_path))),
+            version,
+            path: version_path,
+            integrity: None,
+            sealed: false,
+            parent: None,
+            scheme: None,
+            prerelease: false,
+        })
+        .collect();
+    Ok(Program::new(name, versions))
+}
+
+/// Scans `<root>/<program>/<version>` in parallel, calling `on_program` for
+/// each program as soon as it is complete. With `deep` set, the full module
+/// tree of every version is read as well.
+pub fn scan<F>(root: &Path, deep: bool, on_program: F) -> Result<Vec<Program>>
+where
+    F: Fn(&Program) + Sync,
+{
+    fs::create_dir_all(root)?;
+    let mut programs = list_subdirs(root)?
+        .into_par_iter()
+        .filter_map(
+            |(name, path)| match scan_program(name, Path::new(&path), deep) {
+                Ok(program) => {
+                    on_program(&program);
+                    Some(program)
+                }
+                Err(e) => {
+                    eprintln!("Error scanning program {:?}: {}", path, e);
+                    None
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+    programs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(programs)
+}
+
+/// Fills in the per-version state that is not part of the directory tree:
+/// the last scrub result, whether the version is sealed and its parent.
+pub fn annotate(app: &AppHandle, programs: &mut [Program]) {
+    scrub::annotate(app, programs);
+    for version in programs.iter_mut().flat_map(|p| p.versions.iter_mut()) {
+        let meta = meta::read(Path::new(&version.path)).unwrap_or_default();
+        version.sealed = meta.seal.is_some();
+        version.parent = meta.parent;
+    }
+}
+
+#[tauri::command(async)]
+pub fn scan_catalog(app: AppHandle, deep: Option<bool>) -> Result<Vec<Program>> {
+    let config = config::load(&app)?;
+    let scan_id = NEXT_SCAN_ID.fetch_add(1, AtomicOrdering::Relaxed);
+
+    let mut programs = scan(&config.local_root(), deep.unwrap_or(false), |program| {
+        let mut program = [program.clone()];
+        annotate(&app, &mut program);
+        let _ = app.emit_all(
+            SCAN_PROGRAM_EVENT,
+            ScanProgramPayload {
+                scan_id,
+                program: &program[0],
+            },
+        );
+    })?;
+    annotate(&app, &mut programs);
+
+    let _ = app.emit_all(
+        SCAN_FINISHED_EVENT,
+        ScanFinishedPayload {
+            scan_id,
+            program_count: programs.len(),
+        },
+    );
+    Ok(programs)
+}
@@ -0,0 +1,458 @@
+use crate::archive;
+use crate::catalog::VERSION_META_DIR;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::meta::{self, ParentVersion, VersionMeta};
+use crate::names;
+use crate::scope::Scope;
+use crate::seal;
+use crate::staging::{self, Staging};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const CLONE_PROGRESS_EVENT: &str = "version-clone-progress";
+
+/// Copies report progress in steps of this many bytes.
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+
+/// How file data is carried over to the new tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloneMode {
+    /// Independent copies.
+    #[default]
+    Copy,
+    /// Hard links to the parent's files. Tools that rewrite a file in place
+    /// change it in both versions.
+    Hardlink,
+    /// Copy-on-write clones where the filesystem supports them (Btrfs, XFS,
+    /// APFS), plain copies elsewhere.
+    Reflink,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneProgress {
+    pub program: String,
+    pub version: String,
+    pub entry: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneReport {
+    pub program: String,
+    pub version: String,
+    pub parent: String,
+    pub path: String,
+    pub mode: CloneMode,
+    pub files: usize,
+    pub bytes: u64,
+    /// Files that share their data with the parent instead of being copied.
+    pub linked: usize,
+    pub skipped_links: Vec<String>,
+    pub duration_ms: u128,
+}
+
+enum Entry {
+    Dir,
+    File(u64),
+    Symlink(PathBuf),
+}
+
+/// Lists everything below `dir`, parents before children.
+fn list_entries(dir: &Path, relative: &Path, entries: &mut Vec<(PathBuf, Entry)>) -> Result<()> {
+    let mut children = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|e| e.file_name());
+    for child in children {
+        let child_relative = relative.join(child.file_name());
+        if fsutil::is_temp_file(&child.path()) {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(child.path())?;
+        if metadata.is_symlink() {
+            let target = fs::read_link(child.path())?;
+            entries.push((child_relative, Entry::Symlink(target)));
+        } else if metadata.is_dir() {
+            entries.push((child_relative.clone(), Entry::Dir));
+            list_entries(&child.path(), &child_relative, entries)?;
+        } else if metadata.is_file() {
+            entries.push((child_relative, Entry::File(metadata.len())));
+        }
+    }
+    Ok(())
+}
+
+/// Makes `dest` share the data blocks of `src`. Fails with `Unsupported`
+/// where the filesystem cannot do that.
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let from = fs::File::open(src)?;
+    let to = fs::File::create(dest)?;
+    // SAFETY: FICLONE takes the source descriptor as its argument; both
+    // files stay open for the duration of the call.
+    let rc = unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) };
+    if rc == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    drop(to);
+    let _ = fs::remove_file(dest);
+    match err.raw_os_error() {
+        Some(libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL | libc::ENOTTY) => {
+            Err(io::Error::new(io::ErrorKind::Unsupported, err))
+        }
+        _ => Err(err),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let from = CString::new(src.as_os_str().as_bytes())?;
+    let to = CString::new(dest.as_os_str().as_bytes())?;
+    // SAFETY: both pointers are valid NUL-terminated paths.
+    let rc = unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) };
+    if rc == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::ENOTSUP | libc::EXDEV) => Err(io::Error::new(io::ErrorKind::Unsupported, err)),
+        _ => Err(err),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are not supported on this platform",
+    ))
+}
+
+pub struct TreeCopy {
+    pub files: usize,
+    pub bytes: u64,
+    pub linked: usize,
+    /// Symlinks that point outside the tree or cannot be created on this
+    /// platform.
+    pub skipped_links: Vec<String>,
+}
+
+/// Copies `source` into `dest` according to `mode`, preserving modification
+/// times and permissions. Symlinks are recreated if they stay inside the
+/// tree. The manifest is always copied, so rewriting it for the new version
+/// cannot touch the source.
+pub fn copy_tree<F>(
+    source: &Path,
+    dest: &Path,
+    mode: CloneMode,
+    mut on_progress: F,
+) -> Result<TreeCopy>
+where
+    F: FnMut(&str, u64, u64),
+{
+    let mut entries = Vec::new();
+    list_entries(source, Path::new(""), &mut entries)?;
+    // A link may lead through other links, so all of them are resolved
+    // together.
+    let links: HashMap<PathBuf, PathBuf> = entries
+        .iter()
+        .filter_map(|(relative, entry)| match entry {
+            Entry::Symlink(target) => Some((relative.clone(), target.clone())),
+            _ => None,
+        })
+        .collect();
+    let total: u64 = entries
+        .iter()
+        .map(|(_, entry)| match entry {
+            Entry::File(size) => *size,
+            _ => 0,
+        })
+        .sum();
+
+    fs::create_dir_all(dest)?;
+    let mut copy = TreeCopy {
+        files: 0,
+        bytes: 0,
+        linked: 0,
+        skipped_links: Vec::new(),
+    };
+    let mut can_reflink = mode == CloneMode::Reflink;
+    let mut reported = 0;
+    for (relative, entry) in entries {
+        let key = fsutil::relative_key(&relative);
+        let from = source.join(&relative);
+        let to = dest.join(&relative);
+        match entry {
+            Entry::Dir => fs::create_dir_all(&to)?,
+            Entry::File(size) => {
+                let shareable = !relative.starts_with(VERSION_META_DIR);
+                let linked = if shareable && mode == CloneMode::Hardlink {
+                    fs::hard_link(&from, &to)?;
+                    true
+                } else if shareable && can_reflink {
+                    match reflink(&from, &to) {
+                        Ok(()) => {
+                            let metadata = fs::metadata(&from)?;
+                            fs::set_permissions(&to, metadata.permissions())?;
+                            filetime::set_file_mtime(
+                                &to,
+                                FileTime::from_last_modification_time(&metadata),
+                            )?;
+                            true
+                        }
+                        // The whole tree lives on one filesystem, so there is
+                        // no point in trying again for the next file.
+                        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                            can_reflink = false;
+                            false
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                } else {
+                    false
+                };
+                if linked {
+                    copy.linked += 1;
+                } else {
+                    let done = copy.bytes;
+                    fsutil::copy_file_atomic(&from, &to, |n| {
+                        if done + n >= reported + PROGRESS_STEP {
+                            reported = done + n;
+                            on_progress(&key, reported, total);
+                        }
+                    })?;
+                }
+                copy.bytes += size;
+                copy.files += 1;
+            }
+            #[cfg(unix)]
+            Entry::Symlink(target) if archive::link_stays_inside(&links, &relative) => {
+                std::os::unix::fs::symlink(target, &to)?;
+            }
+            Entry::Symlink(_) => copy.skipped_links.push(key),
+        }
+    }
+    on_progress("", copy.bytes, total);
+    Ok(copy)
+}
+
+/// Copies `<root>/<program>/<parent>` to `<root>/<program>/<version>`
+/// through a staging directory and records the parent in the new version's
+/// metadata. The parent is checked against its manifest on the way, so a
+/// damaged version is never cloned. Clones of sealed versions start out
+/// unsealed and writable.
+pub fn clone<F>(
+    root: &Path,
+    scope: &Scope,
+    program: &str,
+    parent: &str,
+    version: &str,
+    mode: CloneMode,
+    mut on_progress: F,
+) -> Result<CloneReport>
+where
+    F: FnMut(&CloneProgress),
+{
+    let started = Instant::now();
+    let source = scope.version_dir(root, program, parent)?;
+    staging::ensure_absent(root, program, version)?;
+    let sealed = seal::is_sealed(&source)?;
+    if sealed && mode == CloneMode::Hardlink {
+        // Making the clone writable would unprotect the sealed files too.
+        return Err(Error::Sealed(format!(
+            "{}/{} cannot be hardlinked, use a copy or reflink clone",
+            program, parent
+        )));
+    }
+
+    let staging = Staging::new(root, "clone")?;
+    let content = staging.content();
+    let copy = copy_tree(&source, &content, mode, |entry, bytes_done, bytes_total| {
+        on_progress(&CloneProgress {
+            program: program.to_string(),
+            version: version.to_string(),
+            entry: entry.to_string(),
+            bytes_done,
+            bytes_total,
+        });
+    })?;
+    if sealed {
+        for file in fsutil::walk_version(&content)? {
+            fsutil::set_readonly(&file.path, false)?;
+        }
+    }
+    archive::check_manifest(&content, program, version)?;
+    meta::write(
+        &content,
+        &VersionMeta {
+            parent: Some(ParentVersion {
+                program: program.to_string(),
+                version: parent.to_string(),
+                cloned_at: fsutil::now_secs(),
+            }),
+            ..VersionMeta::default()
+        },
+    )?;
+    let target = staging::install(root, scope, &content, program, version)?;
+
+    Ok(CloneReport {
+        program: program.to_string(),
+        version: version.to_string(),
+        parent: parent.to_string(),
+        path: target.to_string_lossy().into_owned(),
+        mode,
+        files: copy.files,
+        bytes: copy.bytes,
+        linked: copy.linked,
+        skipped_links: copy.skipped_links,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[tauri::command(async)]
+pub fn clone_version(
+    app: AppHandle,
+    program: String,
+    parent: String,
+    version: String,
+    mode: Option<CloneMode>,
+) -> Result<CloneReport> {
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    clone(
+        &config.local_root(),
+        &scope,
+        &program,
+        &parent,
+        &names::normalize(&version)?,
+        mode.unwrap_or_default(),
+        |progress| {
+            let _ = app.emit_all(CLONE_PROGRESS_EVENT, progress);
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::manifest;
+    use crate::testutil::{self, TempDir};
+
+    fn local_root(dir: &TempDir) -> (PathBuf, Scope) {
+        let root = dir.join("local");
+        let parent = root.join("app/1.0");
+        testutil::write(&parent.join("app.bin"), "binary");
+        testutil::write(&parent.join("docs/readme.txt"), "readme");
+        manifest::generate(&parent, "app", "1.0").unwrap();
+        let scope = Scope::from_config(&AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        (root, scope)
+    }
+
+    fn clone_as(root: &Path, scope: &Scope, version: &str, mode: CloneMode) -> Result<CloneReport> {
+        clone(root, scope, "app", "1.0", version, mode, |_| {})
+    }
+
+    #[test]
+    fn a_copy_is_independent_and_knows_its_parent() {
+        let dir = TempDir::new("clone");
+        let (root, scope) = local_root(&dir);
+        let report = clone_as(&root, &scope, "1.1", CloneMode::Copy).unwrap();
+        assert_eq!((report.files, report.linked), (3, 0));
+
+        let cloned = root.join("app/1.1");
+        testutil::write(&cloned.join("app.bin"), "patched");
+        assert_eq!(
+            testutil::read(&root.join("app/1.0/app.bin")).as_deref(),
+            Some("binary")
+        );
+        let parent = meta::read(&cloned).unwrap().parent.unwrap();
+        assert_eq!(
+            (parent.program.as_str(), parent.version.as_str()),
+            ("app", "1.0")
+        );
+        assert_eq!(manifest::verify(&cloned).unwrap().version, "1.1");
+        assert!(matches!(
+            clone_as(&root, &scope, "1.1", CloneMode::Copy),
+            Err(Error::AlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn hardlinked_clones_share_data_but_not_the_manifest() {
+        let dir = TempDir::new("clone-hardlink");
+        let (root, scope) = local_root(&dir);
+        let report = clone_as(&root, &scope, "1.1", CloneMode::Hardlink).unwrap();
+        assert_eq!(report.linked, 2);
+        assert!(manifest::verify(&root.join("app/1.0")).unwrap().intact);
+        assert!(manifest::verify(&root.join("app/1.1")).unwrap().intact);
+    }
+
+    #[test]
+    fn a_sealed_parent_gives_a_writable_copy_and_no_hard_links() {
+        let dir = TempDir::new("clone-sealed");
+        let (root, scope) = local_root(&dir);
+        seal::seal(&root.join("app/1.0"), "app", "1.0").unwrap();
+        assert!(matches!(
+            clone_as(&root, &scope, "1.1", CloneMode::Hardlink),
+            Err(Error::Sealed(_))
+        ));
+
+        clone_as(&root, &scope, "1.1", CloneMode::Copy).unwrap();
+        let cloned = root.join("app/1.1");
+        assert!(!seal::is_sealed(&cloned).unwrap());
+        assert!(!fs::metadata(cloned.join("app.bin"))
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(fs::metadata(root.join("app/1.0/app.bin"))
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(manifest::verify(&cloned).unwrap().intact);
+    }
+
+    #[test]
+    fn a_damaged_parent_is_not_cloned() {
+        let dir = TempDir::new("clone-damaged");
+        let (root, scope) = local_root(&dir);
+        testutil::write(&root.join("app/1.0/app.bin"), "bitrot!");
+        assert!(clone_as(&root, &scope, "1.1", CloneMode::Copy).is_err());
+        assert!(!root.join("app/1.1").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_links_inside_the_version_are_recreated() {
+        let dir = TempDir::new("clone-links");
+        let source = dir.join("source");
+        testutil::write(&source.join("lib/libapp.so.1"), "library");
+        std::os::unix::fs::symlink("libapp.so.1", source.join("lib/libapp.so")).unwrap();
+        std::os::unix::fs::symlink("../../outside", source.join("lib/escape")).unwrap();
+
+        let copy = copy_tree(&source, &dir.join("dest"), CloneMode::Copy, |_, _, _| {}).unwrap();
+        assert_eq!(copy.skipped_links, ["lib/escape"]);
+        assert_eq!(
+            fs::read_link(dir.join("dest/lib/libapp.so")).unwrap(),
+            Path::new("libapp.so.1")
+        );
+    }
+}
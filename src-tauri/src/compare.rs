@@ -0,0 +1,228 @@
+use crate::config;
+use crate::error::Result;
+use crate::fsutil::{self, WalkedFile};
+use crate::sync;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionStatus {
+    LocalOnly,
+    MirrorOnly,
+    Differs,
+    Identical,
+}
+
+impl VersionStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            VersionStatus::LocalOnly => "localOnly",
+            VersionStatus::MirrorOnly => "mirrorOnly",
+            VersionStatus::Differs => "differs",
+            VersionStatus::Identical => "identical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionComparison {
+    pub program: String,
+    pub version: String,
+    pub status: VersionStatus,
+    pub local_files: usize,
+    pub local_bytes: u64,
+    pub mirror_files: usize,
+    pub mirror_bytes: u64,
+    /// Files missing on one side or different on both.
+    pub differing_files: usize,
+    /// Bytes of the local copies (or mirror copies, when missing locally)
+    /// of the differing files.
+    pub differing_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonReport {
+    pub generated_at: u64,
+    pub local_root: String,
+    pub mirror_root: String,
+    pub hashed: bool,
+    pub versions: Vec<VersionComparison>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+type VersionFiles = BTreeMap<(String, String), BTreeMap<String, WalkedFile>>;
+
+/// Groups the files under a storage root by program and version. Files
+/// that sit directly in the root or a program folder are ignored.
+fn group_by_version(root: &Path) -> Result<VersionFiles> {
+    let mut versions = VersionFiles::new();
+    for file in fsutil::walk_files(root, true)? {
+        let mut parts = file.relative.components();
+        let (Some(program), Some(version)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let rest = parts.as_path();
+        if rest.as_os_str().is_empty() {
+            continue;
+        }
+        let key = (
+            program.as_os_str().to_string_lossy().into_owned(),
+            version.as_os_str().to_string_lossy().into_owned(),
+        );
+        versions
+            .entry(key)
+            .or_default()
+            .insert(fsutil::relative_key(rest), file);
+    }
+    Ok(versions)
+}
+
+fn files_differ(local: &WalkedFile, mirror: &WalkedFile, hash: bool) -> Result<bool> {
+    if local.metadata.len() != mirror.metadata.len() {
+        return Ok(true);
+    }
+    if hash {
+        return Ok(fsutil::sha256_file(&local.path)? != fsutil::sha256_file(&mirror.path)?);
+    }
+    Ok(!fsutil::same_mtime(&local.metadata, &mirror.metadata))
+}
+
+fn totals(files: Option<&BTreeMap<String, WalkedFile>>) -> (usize, u64) {
+    files
+        .map(|f| (f.len(), f.values().map(|w| w.metadata.len()).sum()))
+        .unwrap_or((0, 0))
+}
+
+/// Compares every program/version under the two roots. Content is compared
+/// by size and mtime, or by SHA-256 when `hash` is set.
+pub fn compare(local: &Path, mirror: &Path, hash: bool) -> Result<ComparisonReport> {
+    let local_versions = group_by_version(local)?;
+    let mirror_versions = group_by_version(mirror)?;
+    let keys: BTreeSet<_> = local_versions
+        .keys()
+        .chain(mirror_versions.keys())
+        .cloned()
+        .collect();
+
+    let mut versions = Vec::new();
+    for key in keys {
+        let local_files = local_versions.get(&key);
+        let mirror_files = mirror_versions.get(&key);
+        let (local_count, local_bytes) = totals(local_files);
+        let (mirror_count, mirror_bytes) = totals(mirror_files);
+
+        let mut differing_files = 0;
+        let mut differing_bytes = 0;
+        let empty = BTreeMap::new();
+        let l = local_files.unwrap_or(&empty);
+        let m = mirror_files.unwrap_or(&empty);
+        for name in l.keys().chain(m.keys()).collect::<BTreeSet<_>>() {
+            let differs = match (l.get(name), m.get(name)) {
+                (Some(a), Some(b)) => files_differ(a, b, hash)?,
+                _ => true,
+            };
+            if differs {
+                differing_files += 1;
+                differing_bytes += l
+                    .get(name)
+                    .or(m.get(name))
+                    .map(|f| f.metadata.len())
+                    .unwrap_or(0);
+            }
+        }
+
+        let status = match (local_files, mirror_files) {
+            (Some(_), None) => VersionStatus::LocalOnly,
+            (None, Some(_)) => VersionStatus::MirrorOnly,
+            _ if differing_files > 0 => VersionStatus::Differs,
+            _ => VersionStatus::Identical,
+        };
+        versions.push(VersionComparison {
+            program: key.0,
+            version: key.1,
+            status,
+            local_files: local_count,
+            local_bytes,
+            mirror_files: mirror_count,
+            mirror_bytes,
+            differing_files,
+            differing_bytes,
+        });
+    }
+
+    Ok(ComparisonReport {
+        generated_at: fsutil::now_secs(),
+        local_root: local.to_string_lossy().into_owned(),
+        mirror_root: mirror.to_string_lossy().into_owned(),
+        hashed: hash,
+        versions,
+    })
+}
+
+pub fn to_csv(report: &ComparisonReport) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "program",
+            "version",
+            "status",
+            "localFiles",
+            "localBytes",
+            "mirrorFiles",
+            "mirrorBytes",
+            "differingFiles",
+            "differingBytes",
+        ])
+        .map_err(io::Error::from)?;
+    for v in &report.versions {
+        writer
+            .write_record([
+                v.program.clone(),
+                v.version.clone(),
+                v.status.as_str().to_string(),
+                v.local_files.to_string(),
+                v.local_bytes.to_string(),
+                v.mirror_files.to_string(),
+                v.mirror_bytes.to_string(),
+                v.differing_files.to_string(),
+                v.differing_bytes.to_string(),
+            ])
+            .map_err(io::Error::from)?;
+    }
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+#[tauri::command(async)]
+pub fn compare_mirror(app: AppHandle, hash: Option<bool>) -> Result<ComparisonReport> {
+    let config = config::load(&app)?;
+    let (local, mirror) = sync::mirror_roots(&config)?;
+    compare(&local, &mirror, hash.unwrap_or(false))
+}
+
+/// Writes a report previously returned by `compare_mirror` to `path`.
+#[tauri::command]
+pub fn export_comparison(
+    report: ComparisonReport,
+    format: ExportFormat,
+    path: String,
+) -> Result<()> {
+    let bytes = match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&report)?,
+        ExportFormat::Csv => to_csv(&report)?,
+    };
+    fs::write(path, bytes)?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+const CONFIG_FILE: &str = "config.json";
+
+fn default_local_path() -> String {
+    "C:\\ProgramManager".to_string()
+}
+
+fn default_trash_retention_days() -> u64 {
+    30
+}
+
+fn default_scrub_interval_hours() -> u64 {
+    24 * 7
+}
+
+/// Mirrors the `AppConfig` the frontend writes to `<appDataDir>/config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppConfig {
+    #[serde(default = "default_local_path")]
+    pub local_path: String,
+    #[serde(default)]
+    pub mirror_path: String,
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u64,
+    /// Hours between background integrity scrubs; zero disables them.
+    #[serde(default = "default_scrub_interval_hours")]
+    pub scrub_interval_hours: u64,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            local_path: default_local_path(),
+            mirror_path: String::new(),
+            trash_retention_days: default_trash_retention_days(),
+            scrub_interval_hours: default_scrub_interval_hours(),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn local_root(&self) -> PathBuf {
+        PathBuf::from(&self.local_path)
+    }
+
+    pub fn mirror_root(&self) -> PathBuf {
+        PathBuf::from(&self.mirror_path)
+    }
+}
+
+pub fn app_data_dir(app: &AppHandle) -> Result<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| Error::Config("app data directory is unavailable".into()))
+}
+
+/// Loads the saved config, falling back to the same defaults the frontend uses
+/// when no config file has been written yet.
+pub fn load(app: &AppHandle) -> Result<AppConfig> {
+    let path = app_data_dir(app)?.join(CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| Error::Config(e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AppConfig::default()),
+        Err(e) => Err(e.into()),
+    }
+}
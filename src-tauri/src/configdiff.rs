@@ -0,0 +1,320 @@
+use crate::diff::ChangeKind;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+    Ini,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyChange {
+    /// Dotted path to the key, such as `server.ports[0]`. Keys that are not
+    /// plain identifiers are quoted.
+    pub path: String,
+    pub change: ChangeKind,
+    /// Compact JSON of the value on each side.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigDiff {
+    pub format: ConfigFormat,
+    /// Sorted by path: the keys of every table are visited in sorted order
+    /// and list items by index. An added or removed table is one change
+    /// rather than one per key inside it.
+    pub changes: Vec<KeyChange>,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+}
+
+/// The config format a file extension stands for.
+pub fn format_of(path: &Path) -> Option<ConfigFormat> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "json" => ConfigFormat::Json,
+        "toml" => ConfigFormat::Toml,
+        "yaml" | "yml" => ConfigFormat::Yaml,
+        "ini" | "cfg" | "conf" => ConfigFormat::Ini,
+        _ => return None,
+    })
+}
+
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => {
+            Value::Object(table.into_iter().map(|(k, v)| (k, from_toml(v))).collect())
+        }
+    }
+}
+
+/// YAML keys may be numbers or booleans; they are compared by their text.
+fn from_yaml(value: serde_yaml::Value) -> Option<Value> {
+    Some(match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => serde_json::to_value(n).ok()?,
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(items) => Value::Array(
+            items
+                .into_iter()
+                .map(from_yaml)
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (k, v) in mapping {
+                let key = match from_yaml(k)? {
+                    Value::String(s) => s,
+                    Value::Null | Value::Array(_) | Value::Object(_) => return None,
+                    other => other.to_string(),
+                };
+                object.insert(key, from_yaml(v)?);
+            }
+            Value::Object(object)
+        }
+        serde_yaml::Value::Tagged(tagged) => from_yaml(tagged.value)?,
+    })
+}
+
+/// Sections become tables; keys before the first section are top-level.
+/// Every line must be blank, a comment, a `[section]` or a `key = value`
+/// (or `key: value`) pair, otherwise the text is not INI.
+fn parse_ini(text: &str) -> Option<Value> {
+    let mut root = Map::new();
+    let mut section: Option<String> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name.strip_suffix(']')?.trim().to_string();
+            root.entry(name.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object()?;
+            section = Some(name);
+            continue;
+        }
+        let (key, value) = line.split_once(['=', ':'])?;
+        let key = key.trim();
+        if key.is_empty() {
+            return None;
+        }
+        let table = match &section {
+            Some(name) => root.get_mut(name)?.as_object_mut()?,
+            None => &mut root,
+        };
+        table.insert(key.to_string(), Value::String(value.trim().to_string()));
+    }
+    Some(Value::Object(root))
+}
+
+/// Parses `text` as `format`. YAML and INI accept almost any text, so only
+/// documents that are a table or a list count.
+pub fn parse(format: ConfigFormat, text: &str) -> Option<Value> {
+    let value = match format {
+        ConfigFormat::Json => serde_json::from_str(text).ok()?,
+        ConfigFormat::Toml => from_toml(toml::from_str(text).ok()?),
+        ConfigFormat::Yaml => from_yaml(serde_yaml::from_str(text).ok()?)?,
+        ConfigFormat::Ini => parse_ini(text)?,
+    };
+    matches!(value, Value::Object(_) | Value::Array(_)).then_some(value)
+}
+
+fn key_path(parent: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-'));
+    let key = if plain {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    };
+    if parent.is_empty() {
+        key
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn change(path: String, old: Option<&Value>, new: Option<&Value>) -> KeyChange {
+    KeyChange {
+        path,
+        change: match (old, new) {
+            (None, _) => ChangeKind::Added,
+            (_, None) => ChangeKind::Removed,
+            _ => ChangeKind::Modified,
+        },
+        old_value: old.map(Value::to_string),
+        new_value: new.map(Value::to_string),
+    }
+}
+
+fn walk(path: &str, old: &Value, new: &Value, changes: &mut Vec<KeyChange>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            // Key order does not matter, so keys are visited sorted.
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let child = key_path(path, key);
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => walk(&child, x, y, changes),
+                    (x, y) => changes.push(change(child, x, y)),
+                }
+            }
+        }
+        // Lists are compared item by item: their order usually matters.
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let child = format!("{}[{}]", path, i);
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => walk(&child, x, y, changes),
+                    (x, y) => changes.push(change(child, x, y)),
+                }
+            }
+        }
+        _ if old != new => changes.push(change(path.to_string(), Some(old), Some(new))),
+        _ => {}
+    }
+}
+
+/// Formats tried, in this order, on files whose extension names none.
+/// Stricter formats come first: JSON is also YAML, and most INI files are
+/// also TOML.
+const FALLBACK_ORDER: [ConfigFormat; 4] = [
+    ConfigFormat::Json,
+    ConfigFormat::Toml,
+    ConfigFormat::Yaml,
+    ConfigFormat::Ini,
+];
+
+/// Key-level diff of two config files. The format comes from the
+/// extensions, which must agree where both have a known one; files without
+/// one get the first format of `FALLBACK_ORDER` both sides parse as. `None`
+/// means the caller should fall back to the line diff.
+pub fn diff(old_path: &Path, old: &str, new_path: &Path, new: &str) -> Option<ConfigDiff> {
+    let formats = match (format_of(old_path), format_of(new_path)) {
+        (Some(a), Some(b)) if a != b => return None,
+        (Some(format), _) | (_, Some(format)) => vec![format],
+        (None, None) => FALLBACK_ORDER.to_vec(),
+    };
+    let (format, old, new) = formats
+        .into_iter()
+        .find_map(|format| Some((format, parse(format, old)?, parse(format, new)?)))?;
+
+    let mut changes = Vec::new();
+    walk("", &old, &new, &mut changes);
+    let count = |kind: ChangeKind| changes.iter().filter(|c| c.change == kind).count();
+    Some(ConfigDiff {
+        format,
+        added: count(ChangeKind::Added),
+        removed: count(ChangeKind::Removed),
+        modified: count(ChangeKind::Modified),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(name: &str, old: &str, new: &str) -> Option<(ConfigFormat, Vec<String>)> {
+        let path = Path::new(name);
+        let diff = diff(path, old, path, new)?;
+        let changes = diff
+            .changes
+            .iter()
+            .map(|c| format!("{:?} {}", c.change, c.path))
+            .collect();
+        Some((diff.format, changes))
+    }
+
+    #[test]
+    fn diffs_json() {
+        let old = r#"{"name": "app", "ports": [80, 443], "debug": true}"#;
+        let new = r#"{"ports": [80, 8443], "name": "app", "log": {"level": "info"}}"#;
+        let (format, found) = changes("settings.json", old, new).unwrap();
+        assert_eq!(format, ConfigFormat::Json);
+        assert_eq!(found, ["Removed debug", "Added log", "Modified ports[1]"]);
+    }
+
+    #[test]
+    fn diffs_toml() {
+        let old = "[server]\nhost = \"localhost\"\nport = 80\n";
+        let new = "[server]\nport = 8080\nhost = \"localhost\"\n\n[\"my key\"]\na = 1\n";
+        let (format, found) = changes("Config.TOML", old, new).unwrap();
+        assert_eq!(format, ConfigFormat::Toml);
+        assert_eq!(found, ["Added \"my key\"", "Modified server.port"]);
+    }
+
+    #[test]
+    fn diffs_yaml() {
+        let old = "name: app\nfeatures:\n  - a\n  - b\n1: one\n";
+        let new = "name: app\nfeatures:\n  - a\n1: uno\n";
+        let (format, found) = changes("ci.yml", old, new).unwrap();
+        assert_eq!(format, ConfigFormat::Yaml);
+        assert_eq!(found, ["Modified 1", "Removed features[1]"]);
+    }
+
+    #[test]
+    fn diffs_ini() {
+        let old = "; comment\ntop = 1\n[paths]\nhome = /a\n";
+        let new = "top = 1\n[paths]\nhome = /b\ntemp: /tmp\n";
+        let (format, found) = changes("app.ini", old, new).unwrap();
+        assert_eq!(format, ConfigFormat::Ini);
+        assert_eq!(found, ["Modified paths.home", "Added paths.temp"]);
+    }
+
+    #[test]
+    fn reports_values_as_json() {
+        let diff = diff(
+            Path::new("a.json"),
+            r#"{"v": "x"}"#,
+            Path::new("a.json"),
+            r#"{"v": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(diff.modified, 1);
+        assert_eq!(diff.changes[0].old_value.as_deref(), Some("\"x\""));
+        assert_eq!(diff.changes[0].new_value.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn tries_formats_without_a_known_extension() {
+        let json = changes("settings", r#"{"a": 1}"#, r#"{"a": 2}"#).unwrap();
+        assert_eq!(json.0, ConfigFormat::Json);
+        let toml = changes("app.rc", "[s]\nk = \"x\"\n", "[s]\nk = \"y\"\n").unwrap();
+        assert_eq!(toml.0, ConfigFormat::Toml);
+        let yaml = changes("compose", "a:\n  b: 1\n", "a:\n  b: 2\n").unwrap();
+        assert_eq!(yaml.0, ConfigFormat::Yaml);
+        let ini = changes("setup.rc", "[s]\nk = x y\n", "[s]\nk = x z\n").unwrap();
+        assert_eq!(ini.0, ConfigFormat::Ini);
+    }
+
+    #[test]
+    fn falls_back_to_the_line_diff() {
+        assert!(changes("broken.json", "{", "{}").is_none());
+        assert!(changes("notes.txt", "just some text", "other text").is_none());
+        assert!(diff(Path::new("a.json"), "{}", Path::new("a.toml"), "").is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::archive;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::names;
+use crate::scope::{plain_name, Scope};
+use crate::staging::{self, Staging};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Creates the empty directory `<root>/<program>`.
+pub fn new_program(root: &Path, scope: &Scope, program: &str) -> Result<PathBuf> {
+    let name = names::validate(program)?;
+    if let Some(existing) = names::find_equivalent(root, name)? {
+        return Err(Error::AlreadyExists(existing));
+    }
+    let dir = scope.check(&root.join(name))?;
+    if fs::symlink_metadata(&dir).is_ok() {
+        return Err(Error::AlreadyExists(name.to_string()));
+    }
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Creates an empty version with its manifest. Like versions that are
+/// imported or cloned, it is assembled in a staging directory and only
+/// appears in the catalog once complete.
+pub fn new_version(root: &Path, scope: &Scope, program: &str, version: &str) -> Result<PathBuf> {
+    let program_dir = scope.check(&root.join(plain_name(program)?))?;
+    if !program_dir.is_dir() {
+        return Err(Error::NotFound(program.to_string()));
+    }
+    staging::ensure_absent(root, program, version)?;
+
+    let staging = Staging::new(root, "create")?;
+    let content = staging.content();
+    fs::create_dir_all(&content)?;
+    archive::check_manifest(&content, program, version)?;
+    staging::install(root, scope, &content, program, version)
+}
+
+/// Loads the config and makes sure the storage root exists, so the first
+/// program can be created on a fresh install.
+fn local_root(app: &AppHandle) -> Result<(PathBuf, Scope)> {
+    let config = config::load(app)?;
+    let root = config.local_root();
+    fs::create_dir_all(&root)?;
+    let scope = Scope::from_config(&config)?;
+    Ok((fs::canonicalize(&root)?, scope))
+}
+
+#[tauri::command]
+pub fn create_program(app: AppHandle, name: String) -> Result<String> {
+    let (root, scope) = local_root(&app)?;
+    let dir = new_program(&root, &scope, &names::normalize(&name)?)?;
+    Ok(dir.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn create_version(app: AppHandle, program: String, version: String) -> Result<String> {
+    let (root, scope) = local_root(&app)?;
+    let dir = new_version(&root, &scope, &program, &names::normalize(&version)?)?;
+    Ok(dir.to_string_lossy().into_owned())
+}
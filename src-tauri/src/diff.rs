@@ -0,0 +1,220 @@
+use crate::config;
+use crate::error::Result;
+use crate::fsutil::{self, WalkedFile};
+use crate::scope::Scope;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::Instant;
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    /// `/`-separated path relative to the version directories.
+    pub path: String,
+    pub change: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_size: Option<u64>,
+    pub size_delta: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionDiff {
+    pub program: String,
+    pub from: String,
+    pub to: String,
+    /// Sorted by path, so the UI can render them as a tree.
+    pub changes: Vec<FileChange>,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub unchanged: usize,
+    /// Total size of `to` minus total size of `from`.
+    pub size_delta: i64,
+    /// Pairs of files whose sizes matched and that had to be hashed.
+    pub files_hashed: usize,
+    pub duration_ms: u128,
+}
+
+fn by_key(dir: &Path) -> Result<BTreeMap<String, WalkedFile>> {
+    Ok(fsutil::walk_version(dir)?
+        .into_iter()
+        .map(|f| (fsutil::relative_key(&f.relative), f))
+        .collect())
+}
+
+fn delta(old: u64, new: u64) -> i64 {
+    new as i64 - old as i64
+}
+
+/// Compares two version directories file by file. Files of different sizes
+/// are changed; files of the same size are compared by SHA-256, in
+/// parallel. The metadata directory is not compared.
+pub fn diff(
+    program: &str,
+    from: &str,
+    from_dir: &Path,
+    to: &str,
+    to_dir: &Path,
+) -> Result<VersionDiff> {
+    let started = Instant::now();
+    let old = by_key(from_dir)?;
+    let new = by_key(to_dir)?;
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    let mut changes = Vec::new();
+    let mut candidates = Vec::new();
+    for path in paths {
+        match (old.get(path), new.get(path)) {
+            (Some(a), Some(b)) if a.metadata.len() == b.metadata.len() => {
+                candidates.push((path, a, b));
+            }
+            (Some(a), Some(b)) => changes.push(FileChange {
+                path: path.clone(),
+                change: ChangeKind::Modified,
+                old_size: Some(a.metadata.len()),
+                new_size: Some(b.metadata.len()),
+                size_delta: delta(a.metadata.len(), b.metadata.len()),
+            }),
+            (Some(a), None) => changes.push(FileChange {
+                path: path.clone(),
+                change: ChangeKind::Removed,
+                old_size: Some(a.metadata.len()),
+                new_size: None,
+                size_delta: delta(a.metadata.len(), 0),
+            }),
+            (None, Some(b)) => changes.push(FileChange {
+                path: path.clone(),
+                change: ChangeKind::Added,
+                old_size: None,
+                new_size: Some(b.metadata.len()),
+                size_delta: delta(0, b.metadata.len()),
+            }),
+            (None, None) => {}
+        }
+    }
+
+    let files_hashed = candidates.len();
+    let differing: Vec<FileChange> = candidates
+        .par_iter()
+        .map(|(path, a, b)| {
+            let same = fsutil::sha256_file(&a.path)? == fsutil::sha256_file(&b.path)?;
+            Ok((!same).then(|| FileChange {
+                path: (*path).clone(),
+                change: ChangeKind::Modified,
+                old_size: Some(a.metadata.len()),
+                new_size: Some(b.metadata.len()),
+                size_delta: 0,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let unchanged = files_hashed - differing.len();
+    changes.extend(differing);
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let count = |kind: ChangeKind| changes.iter().filter(|c| c.change == kind).count();
+    Ok(VersionDiff {
+        program: program.to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        added: count(ChangeKind::Added),
+        removed: count(ChangeKind::Removed),
+        modified: count(ChangeKind::Modified),
+        unchanged,
+        size_delta: changes.iter().map(|c| c.size_delta).sum(),
+        files_hashed,
+        changes,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+/// Compares `<program>/<from>` with `<program>/<to>` in the local root.
+#[tauri::command(async)]
+pub fn diff_versions(
+    app: AppHandle,
+    program: String,
+    from: String,
+    to: String,
+) -> Result<VersionDiff> {
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    let root = config.local_root();
+    let from_dir = scope.version_dir(&root, &program, &from)?;
+    let to_dir = scope.version_dir(&root, &program, &to)?;
+    diff(&program, &from, &from_dir, &to, &to_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest;
+    use crate::testutil::{self, TempDir};
+
+    #[test]
+    fn classifies_changes_by_size_and_content() {
+        let dir = TempDir::new("diff");
+        let (old, new) = (dir.join("1.0"), dir.join("2.0"));
+        for (name, a, b) in [
+            ("same.txt", Some("same"), Some("same")),
+            ("resized.txt", Some("short"), Some("much longer")),
+            ("edited.txt", Some("abcd"), Some("abce")),
+            ("gone/old.txt", Some("old"), None),
+            ("docs/new.txt", None, Some("new")),
+        ] {
+            if let Some(a) = a {
+                testutil::write(&old.join(name), a);
+            }
+            if let Some(b) = b {
+                testutil::write(&new.join(name), b);
+            }
+        }
+        // Manifests differ between versions but are not compared.
+        manifest::generate(&old, "app", "1.0").unwrap();
+        manifest::generate(&new, "app", "2.0").unwrap();
+
+        let diff = diff("app", "1.0", &old, "2.0", &new).unwrap();
+        let changes: Vec<_> = diff
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.change, c.size_delta))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("docs/new.txt", ChangeKind::Added, 3),
+                ("edited.txt", ChangeKind::Modified, 0),
+                ("gone/old.txt", ChangeKind::Removed, -3),
+                ("resized.txt", ChangeKind::Modified, 6),
+            ]
+        );
+        assert_eq!((diff.added, diff.removed, diff.modified), (1, 1, 2));
+        assert_eq!((diff.unchanged, diff.files_hashed), (1, 2));
+        assert_eq!(diff.size_delta, 6);
+    }
+
+    #[test]
+    fn identical_versions_have_no_changes() {
+        let dir = TempDir::new("diff-same");
+        testutil::write(&dir.join("1.0/app.bin"), "binary");
+        testutil::write(&dir.join("1.1/app.bin"), "binary");
+        let diff = diff("app", "1.0", &dir.join("1.0"), "1.1", &dir.join("1.1")).unwrap();
+        assert!(diff.changes.is_empty());
+        assert_eq!((diff.unchanged, diff.size_delta), (1, 0));
+    }
+}
@@ -0,0 +1,72 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::io;
+
+/// Errors returned by commands. They serialize as `{ kind, message }` so the
+/// frontend can branch on `kind` and show `message` to the user.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("catalog index error: {0}")]
+    Index(String),
+    #[error("file watcher error: {0}")]
+    Watcher(String),
+    #[error("internal state error: {0}")]
+    State(String),
+    #[error("busy: {0}")]
+    Busy(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("already exists: {0}")]
+    AlreadyExists(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("path is outside the configured storage roots: {0}")]
+    OutsideScope(String),
+    #[error("refusing to operate on a storage root: {0}")]
+    ScopeRoot(String),
+    #[error("version is sealed: {0}")]
+    Sealed(String),
+    #[error("mirror not available: {0}")]
+    MirrorUnavailable(String),
+    #[error("confirmation required: {0}")]
+    NeedsConfirmation(String),
+}
+
+impl Error {
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::Config(_) => "config",
+            Error::Index(_) => "index",
+            Error::Watcher(_) => "watcher",
+            Error::State(_) => "state",
+            Error::Busy(_) => "busy",
+            Error::NotFound(_) => "notFound",
+            Error::AlreadyExists(_) => "alreadyExists",
+            Error::InvalidArgument(_) => "invalidArgument",
+            Error::OutsideScope(_) => "outsideScope",
+            Error::ScopeRoot(_) => "scopeRoot",
+            Error::Sealed(_) => "sealed",
+            Error::MirrorUnavailable(_) => "mirrorUnavailable",
+            Error::NeedsConfirmation(_) => "needsConfirmation",
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Error", 2)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
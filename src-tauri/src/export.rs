@@ -0,0 +1,566 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::catalog::{self, VERSION_META_DIR};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::manifest::{Manifest, ManifestEntry, MANIFEST_FILE};
+use crate::scope::{plain_name, Scope};
+use filetime::FileTime;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const EXPORT_PROGRESS_EVENT: &str = "archive-export-progress";
+
+/// Progress is reported at most once per this many bytes of a file.
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub entry: String,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub path: String,
+    pub format: ArchiveFormat,
+    pub versions: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub duration_ms: u128,
+}
+
+enum Kind {
+    Dir,
+    File,
+    Symlink(PathBuf),
+}
+
+/// One entry to write, named by its `/`-separated path in the archive.
+struct Item {
+    name: String,
+    path: PathBuf,
+    kind: Kind,
+    mtime: i64,
+    mode: u32,
+}
+
+struct VersionPlan {
+    program: String,
+    version: String,
+    dir: PathBuf,
+    /// Archive path of the version directory, ending in `/`.
+    prefix: String,
+    items: Vec<Item>,
+}
+
+fn mtime_secs(meta: &Metadata) -> i64 {
+    FileTime::from_last_modification_time(meta).unix_seconds()
+}
+
+#[cfg(unix)]
+fn dir_mode(meta: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn dir_mode(_meta: &Metadata) -> u32 {
+    0o755
+}
+
+/// Lists a version's directories, files and symlinks below `prefix`,
+/// leaving out its metadata directory and interrupted copies.
+fn plan_dir(dir: &Path, prefix: &str, top: bool, items: &mut Vec<Item>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if (top && file_name == VERSION_META_DIR) || fsutil::is_temp_file(&entry.path()) {
+            continue;
+        }
+        let meta = fs::symlink_metadata(entry.path())?;
+        let name = format!("{}{}", prefix, file_name);
+        let kind = if meta.file_type().is_symlink() {
+            Kind::Symlink(fs::read_link(entry.path())?)
+        } else if meta.is_dir() {
+            Kind::Dir
+        } else if meta.is_file() {
+            Kind::File
+        } else {
+            continue;
+        };
+        let is_dir = matches!(kind, Kind::Dir);
+        items.push(Item {
+            name: if is_dir {
+                format!("{}/", name)
+            } else {
+                name.clone()
+            },
+            path: entry.path(),
+            mode: if is_dir {
+                dir_mode(&meta)
+            } else {
+                fsutil::file_mode(&meta)
+            },
+            mtime: mtime_secs(&meta),
+            kind,
+        });
+        if is_dir {
+            plan_dir(&entry.path(), &format!("{}/", name), false, items)?;
+        }
+    }
+    Ok(())
+}
+
+/// Hashes and counts the data as the archive writer pulls it.
+struct HashingReader<'a, R> {
+    inner: R,
+    hasher: Sha256,
+    read: u64,
+    on_read: &'a mut dyn FnMut(u64),
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.read += n as u64;
+        (self.on_read)(self.read);
+        Ok(n)
+    }
+}
+
+/// The two container formats behind a common interface.
+trait Sink {
+    fn dir(&mut self, name: &str, mtime: i64, mode: u32) -> io::Result<()>;
+    fn file(
+        &mut self,
+        name: &str,
+        size: u64,
+        mtime: i64,
+        mode: u32,
+        data: &mut dyn Read,
+    ) -> io::Result<()>;
+    fn symlink(&mut self, name: &str, target: &Path, mtime: i64) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<File>;
+}
+
+enum Compressor {
+    Gz(flate2::write::GzEncoder<File>),
+    Zst(zstd::Encoder<'static, File>),
+}
+
+impl Write for Compressor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::Gz(w) => w.write(buf),
+            Compressor::Zst(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::Gz(w) => w.flush(),
+            Compressor::Zst(w) => w.flush(),
+        }
+    }
+}
+
+struct TarSink(tar::Builder<Compressor>);
+
+impl TarSink {
+    fn header(kind: tar::EntryType, size: u64, mtime: i64, mode: u32) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(size);
+        header.set_mtime(mtime.max(0) as u64);
+        header.set_mode(mode);
+        header
+    }
+}
+
+impl Sink for TarSink {
+    fn dir(&mut self, name: &str, mtime: i64, mode: u32) -> io::Result<()> {
+        let mut header = Self::header(tar::EntryType::Directory, 0, mtime, mode);
+        self.0.append_data(&mut header, name, io::empty())
+    }
+
+    fn file(
+        &mut self,
+        name: &str,
+        size: u64,
+        mtime: i64,
+        mode: u32,
+        data: &mut dyn Read,
+    ) -> io::Result<()> {
+        let mut header = Self::header(tar::EntryType::Regular, size, mtime, mode);
+        self.0.append_data(&mut header, name, data)
+    }
+
+    fn symlink(&mut self, name: &str, target: &Path, mtime: i64) -> io::Result<()> {
+        let mut header = Self::header(tar::EntryType::Symlink, 0, mtime, 0o777);
+        self.0.append_link(&mut header, name, target)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<File> {
+        match self.0.into_inner()? {
+            Compressor::Gz(w) => w.finish(),
+            Compressor::Zst(w) => w.finish(),
+        }
+    }
+}
+
+struct ZipSink(zip::ZipWriter<File>);
+
+impl ZipSink {
+    fn options(mtime: i64, mode: u32) -> zip::write::SimpleFileOptions {
+        zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(archive::zip_datetime(mtime))
+            .unix_permissions(mode)
+    }
+}
+
+impl Sink for ZipSink {
+    fn dir(&mut self, name: &str, mtime: i64, mode: u32) -> io::Result<()> {
+        self.0
+            .add_directory(name, Self::options(mtime, mode))
+            .map_err(io::Error::from)
+    }
+
+    fn file(
+        &mut self,
+        name: &str,
+        size: u64,
+        mtime: i64,
+        mode: u32,
+        data: &mut dyn Read,
+    ) -> io::Result<()> {
+        let options = Self::options(mtime, mode).large_file(size >= u64::from(u32::MAX));
+        self.0.start_file(name, options).map_err(io::Error::from)?;
+        io::copy(data, &mut self.0)?;
+        Ok(())
+    }
+
+    fn symlink(&mut self, name: &str, target: &Path, mtime: i64) -> io::Result<()> {
+        self.0
+            .add_symlink(name, target.to_string_lossy(), Self::options(mtime, 0o777))
+            .map_err(io::Error::from)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<File> {
+        self.0.finish().map_err(io::Error::from)
+    }
+}
+
+fn open_sink(format: ArchiveFormat, file: File) -> io::Result<Box<dyn Sink>> {
+    Ok(match format {
+        ArchiveFormat::Zip => Box::new(ZipSink(zip::ZipWriter::new(file))),
+        ArchiveFormat::TarGz => Box::new(TarSink(tar::Builder::new(Compressor::Gz(
+            flate2::write::GzEncoder::new(file, flate2::Compression::default()),
+        )))),
+        ArchiveFormat::TarZst => Box::new(TarSink(tar::Builder::new(Compressor::Zst(
+            zstd::Encoder::new(file, 0)?,
+        )))),
+    })
+}
+
+/// Writes the planned versions to `sink`. Every file is hashed while it is
+/// streamed, and a manifest of exactly what went into the archive is added
+/// as `<version>/.pm/manifest.json` after each version's files.
+fn write_versions<F>(
+    sink: &mut dyn Sink,
+    plans: &[VersionPlan],
+    program_dir: Option<(&str, &Path)>,
+    on_progress: &mut F,
+) -> Result<(usize, u64)>
+where
+    F: FnMut(&ExportProgress),
+{
+    let files_total = plans
+        .iter()
+        .flat_map(|p| &p.items)
+        .filter(|i| matches!(i.kind, Kind::File))
+        .count();
+    let bytes_total = plans
+        .iter()
+        .flat_map(|p| &p.items)
+        .filter(|i| matches!(i.kind, Kind::File))
+        .filter_map(|i| fs::metadata(&i.path).ok())
+        .map(|m| m.len())
+        .sum();
+    let mut progress = ExportProgress {
+        entry: String::new(),
+        files_done: 0,
+        files_total,
+        bytes_done: 0,
+        bytes_total,
+    };
+
+    if let Some((name, dir)) = program_dir {
+        let meta = fs::metadata(dir)?;
+        sink.dir(&format!("{}/", name), mtime_secs(&meta), dir_mode(&meta))?;
+    }
+
+    for plan in plans {
+        let meta = fs::metadata(&plan.dir)?;
+        sink.dir(&plan.prefix, mtime_secs(&meta), dir_mode(&meta))?;
+
+        let mut entries = Vec::new();
+        for item in &plan.items {
+            match &item.kind {
+                Kind::Dir => sink.dir(&item.name, item.mtime, item.mode)?,
+                Kind::Symlink(target) => sink.symlink(&item.name, target, item.mtime)?,
+                Kind::File => {
+                    let file = File::open(&item.path)?;
+                    let size = file.metadata()?.len();
+                    progress.entry = item.name.clone();
+                    let base = progress.bytes_done;
+                    let mut last = 0;
+                    let mut on_read = |read: u64| {
+                        if read - last >= PROGRESS_STEP {
+                            last = read;
+                            progress.bytes_done = base + read;
+                            on_progress(&progress);
+                        }
+                    };
+                    let mut reader = HashingReader {
+                        inner: file.take(size),
+                        hasher: Sha256::new(),
+                        read: 0,
+                        on_read: &mut on_read,
+                    };
+                    sink.file(&item.name, size, item.mtime, item.mode, &mut reader)?;
+                    entries.push(ManifestEntry {
+                        path: item.name[plan.prefix.len()..].to_string(),
+                        size: reader.read,
+                        mode: item.mode,
+                        sha256: hex::encode(reader.hasher.finalize()),
+                    });
+                    progress.bytes_done = base + size;
+                    progress.files_done += 1;
+                    on_progress(&progress);
+                }
+            }
+        }
+
+        let manifest = Manifest::new(&plan.program, &plan.version, entries);
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        let meta_dir = format!("{}{}/", plan.prefix, VERSION_META_DIR);
+        sink.dir(&meta_dir, fsutil::now_secs() as i64, 0o755)?;
+        sink.file(
+            &format!("{}{}", meta_dir, MANIFEST_FILE),
+            json.len() as u64,
+            fsutil::now_secs() as i64,
+            0o644,
+            &mut json.as_slice(),
+        )?;
+    }
+    Ok((progress.files_done, progress.bytes_done))
+}
+
+/// Packs one version (`<version>/...`) or, without `version`, every version
+/// of a program (`<program>/<version>/...`) into `dest`. The archive is
+/// written to a temporary file and renamed into place when complete.
+pub fn export<F>(
+    root: &Path,
+    scope: &Scope,
+    program: &str,
+    version: Option<&str>,
+    format: ArchiveFormat,
+    dest: &Path,
+    mut on_progress: F,
+) -> Result<ExportReport>
+where
+    F: FnMut(&ExportProgress),
+{
+    let started = Instant::now();
+    let program_path = scope.check(&root.join(plain_name(program)?))?;
+    let versions = match version {
+        Some(version) => vec![(
+            version.to_string(),
+            scope.version_dir(root, program, version)?,
+        )],
+        None => catalog::list_subdirs(&program_path)?
+            .into_iter()
+            .map(|(name, path)| (name, PathBuf::from(path)))
+            .collect(),
+    };
+    if versions.is_empty() {
+        return Err(Error::NotFound(format!("{} has no versions", program)));
+    }
+
+    let mut plans = Vec::new();
+    for (name, dir) in &versions {
+        let prefix = match version {
+            Some(_) => format!("{}/", name),
+            None => format!("{}/{}/", program, name),
+        };
+        let mut items = Vec::new();
+        plan_dir(dir, &prefix, true, &mut items)?;
+        plans.push(VersionPlan {
+            program: program.to_string(),
+            version: name.clone(),
+            dir: dir.clone(),
+            prefix,
+            items,
+        });
+    }
+
+    let temp = fsutil::temp_path(dest);
+    let result = (|| -> Result<(usize, u64)> {
+        let mut sink = open_sink(format, File::create(&temp)?)?;
+        let program_dir = version
+            .is_none()
+            .then_some((program, program_path.as_path()));
+        let (files, bytes) = write_versions(sink.as_mut(), &plans, program_dir, &mut on_progress)?;
+        sink.finish()?.sync_all()?;
+        fs::rename(&temp, dest)?;
+        Ok((files, bytes))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    let (files, bytes) = result?;
+
+    Ok(ExportReport {
+        path: dest.to_string_lossy().into_owned(),
+        format,
+        versions: plans.len(),
+        files,
+        bytes,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[tauri::command(async)]
+pub fn export_archive(
+    app: AppHandle,
+    program: String,
+    version: Option<String>,
+    format: ArchiveFormat,
+    dest: String,
+) -> Result<ExportReport> {
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    export(
+        &config.local_root(),
+        &scope,
+        &program,
+        version.as_deref(),
+        format,
+        Path::new(&dest),
+        |progress| {
+            let _ = app.emit_all(EXPORT_PROGRESS_EVENT, progress);
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::manifest;
+    use crate::testutil::{self, TempDir};
+
+    fn local_root(dir: &TempDir) -> (PathBuf, Scope) {
+        let root = dir.join("local");
+        for version in ["1.0", "2.0"] {
+            let version_dir = root.join("app").join(version);
+            testutil::write(&version_dir.join("app.bin"), version);
+            testutil::write(&version_dir.join("docs/readme.txt"), "readme");
+            manifest::generate(&version_dir, "app", version).unwrap();
+        }
+        let scope = Scope::from_config(&AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        (root, scope)
+    }
+
+    #[test]
+    fn an_exported_version_imports_as_an_identical_one() {
+        let dir = TempDir::new("export");
+        let (root, scope) = local_root(&dir);
+        for (format, name) in [
+            (ArchiveFormat::Zip, "app.zip"),
+            (ArchiveFormat::TarGz, "app.tar.gz"),
+            (ArchiveFormat::TarZst, "app.tar.zst"),
+        ] {
+            let dest = dir.join(name);
+            let report = export(&root, &scope, "app", Some("1.0"), format, &dest, |_| {}).unwrap();
+            assert_eq!(report.versions, 1);
+            assert!(!fsutil::temp_path(&dest).exists());
+
+            let imported = format!("1.0-{:?}", format);
+            archive::import(&root, &scope, &dest, "app", &imported, true, |_| {}).unwrap();
+            let copy = root.join("app").join(&imported);
+            assert_eq!(
+                testutil::read(&copy.join("app.bin")).as_deref(),
+                Some("1.0")
+            );
+            let verified = manifest::verify(&copy).unwrap();
+            assert!(verified.intact, "{:?}", format);
+            assert_eq!(verified.version, imported);
+        }
+    }
+
+    #[test]
+    fn a_program_export_holds_every_version() {
+        let dir = TempDir::new("export-program");
+        let (root, scope) = local_root(&dir);
+        let dest = dir.join("app.zip");
+        let report = export(
+            &root,
+            &scope,
+            "app",
+            None,
+            ArchiveFormat::Zip,
+            &dest,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(report.versions, 2);
+
+        let zip = zip::ZipArchive::new(File::open(&dest).unwrap()).unwrap();
+        let names: Vec<_> = zip.file_names().collect();
+        for name in [
+            "app/1.0/app.bin",
+            "app/2.0/docs/readme.txt",
+            "app/2.0/.pm/manifest.json",
+        ] {
+            assert!(names.contains(&name), "{} in {:?}", name, names);
+        }
+    }
+
+    #[test]
+    fn missing_versions_and_odd_names_are_refused() {
+        let dir = TempDir::new("export-missing");
+        let (root, scope) = local_root(&dir);
+        let dest = dir.join("out.zip");
+        let zip = ArchiveFormat::Zip;
+        assert!(matches!(
+            export(&root, &scope, "app", Some("3.0"), zip, &dest, |_| {}),
+            Err(Error::NotFound(_))
+        ));
+        assert!(export(&root, &scope, "../app", None, zip, &dest, |_| {}).is_err());
+        fs::create_dir_all(root.join("empty")).unwrap();
+        assert!(matches!(
+            export(&root, &scope, "empty", None, zip, &dest, |_| {}),
+            Err(Error::NotFound(_))
+        ));
+        assert!(!dest.exists());
+    }
+}
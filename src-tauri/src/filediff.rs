@@ -0,0 +1,783 @@
+use crate::config;
+use crate::configdiff::{self, ConfigDiff};
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::scope::Scope;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Files larger than this are only compared by hash.
+const MAX_TEXT_BYTES: u64 = 4 * 1024 * 1024;
+/// Like git, a NUL byte near the start marks a file as binary.
+const BINARY_PROBE_BYTES: usize = 8000;
+/// After this long the diff settles for a correct but non-minimal result.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_CONTEXT: usize = 3;
+const MAX_HEADER_CHARS: usize = 80;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLayout {
+    #[default]
+    Unified,
+    SideBySide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileDiffKind {
+    Text,
+    /// Only `identical` and the sizes are filled in.
+    Binary,
+    /// Larger than the text diff limit; compared by hash only.
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+    /// Side-by-side only: a removed line shown next to the line that
+    /// replaced it.
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: LineKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SideLine {
+    pub number: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SideBySideRow {
+    pub kind: LineKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<SideLine>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<SideLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    /// 1-based, as in `@@ -old_start,old_lines +new_start,new_lines @@`.
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// The enclosing function, section or heading, as git shows it after
+    /// the `@@` marker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Filled for the unified layout.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<DiffLine>,
+    /// Filled for the side-by-side layout.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rows: Vec<SideBySideRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    pub kind: FileDiffKind,
+    /// Derived from the file extension, for highlighting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'static str>,
+    pub identical: bool,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub added: usize,
+    pub removed: usize,
+    /// The diff took too long and is correct but not minimal.
+    pub approximate: bool,
+    pub hunks: Vec<Hunk>,
+    /// Key-level changes, when both files parse as the same config format.
+    /// The UI shows these instead of the hunks by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<ConfigDiff>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Myers' O(ND) difference algorithm in its linear-space form: the middle
+/// snake of the edit graph splits the problem in two, recursively.
+struct Myers<'a> {
+    old: &'a [u32],
+    new: &'a [u32],
+    vf: Vec<usize>,
+    vb: Vec<usize>,
+    offset: isize,
+    deadline: Instant,
+    approximate: bool,
+    edits: Vec<Edit>,
+}
+
+impl<'a> Myers<'a> {
+    fn diff(old: &'a [u32], new: &'a [u32], deadline: Instant) -> (Vec<Edit>, bool) {
+        let max_d = (old.len() + new.len()).div_ceil(2) + 1;
+        let mut myers = Myers {
+            old,
+            new,
+            vf: vec![0; 2 * max_d + 2],
+            vb: vec![0; 2 * max_d + 2],
+            offset: max_d as isize,
+            deadline,
+            approximate: false,
+            edits: Vec::with_capacity(old.len().max(new.len())),
+        };
+        myers.conquer(0, old.len(), 0, new.len());
+        // Show removed lines before the lines that replace them.
+        for run in myers.edits.split_mut(|e| matches!(e, Edit::Equal(..))) {
+            run.sort_by_key(|e| matches!(e, Edit::Insert(_)));
+        }
+        (myers.edits, myers.approximate)
+    }
+
+    fn at(&self, k: isize) -> usize {
+        (k + self.offset) as usize
+    }
+
+    fn conquer(&mut self, mut old_lo: usize, old_hi: usize, mut new_lo: usize, new_hi: usize) {
+        while old_lo < old_hi && new_lo < new_hi && self.old[old_lo] == self.new[new_lo] {
+            self.edits.push(Edit::Equal(old_lo, new_lo));
+            old_lo += 1;
+            new_lo += 1;
+        }
+        let (mut old_end, mut new_end) = (old_hi, new_hi);
+        while old_end > old_lo && new_end > new_lo && self.old[old_end - 1] == self.new[new_end - 1]
+        {
+            old_end -= 1;
+            new_end -= 1;
+        }
+
+        if old_lo == old_end {
+            self.edits.extend((new_lo..new_end).map(Edit::Insert));
+        } else if new_lo == new_end {
+            self.edits.extend((old_lo..old_end).map(Edit::Delete));
+        } else {
+            match self.middle_snake(old_lo, old_end, new_lo, new_end) {
+                Some((x, y)) if (x, y) != (old_lo, new_lo) && (x, y) != (old_end, new_end) => {
+                    self.conquer(old_lo, x, new_lo, y);
+                    self.conquer(x, old_end, y, new_end);
+                }
+                _ => {
+                    self.approximate = true;
+                    self.edits.extend((old_lo..old_end).map(Edit::Delete));
+                    self.edits.extend((new_lo..new_end).map(Edit::Insert));
+                }
+            }
+        }
+        self.edits
+            .extend((0..old_hi - old_end).map(|i| Edit::Equal(old_end + i, new_end + i)));
+    }
+
+    /// Finds a point on an optimal path through the middle of the edit
+    /// graph of `old[old_lo..old_hi]` and `new[new_lo..new_hi]`, or `None`
+    /// once the deadline has passed.
+    fn middle_snake(
+        &mut self,
+        old_lo: usize,
+        old_hi: usize,
+        new_lo: usize,
+        new_hi: usize,
+    ) -> Option<(usize, usize)> {
+        let n = (old_hi - old_lo) as isize;
+        let m = (new_hi - new_lo) as isize;
+        let delta = n - m;
+        let odd = delta & 1 != 0;
+        let one = self.at(1);
+        self.vf[one] = 0;
+        self.vb[one] = 0;
+
+        for d in 0..(n + m + 1) / 2 + 1 {
+            if Instant::now() > self.deadline {
+                return None;
+            }
+            for k in (-d..=d).rev().step_by(2) {
+                let mut x =
+                    if k == -d || (k != d && self.vf[self.at(k - 1)] < self.vf[self.at(k + 1)]) {
+                        self.vf[self.at(k + 1)] as isize
+                    } else {
+                        self.vf[self.at(k - 1)] as isize + 1
+                    };
+                let mut y = x - k;
+                let (x0, y0) = (x, y);
+                while x < n
+                    && y < m
+                    && self.old[old_lo + x as usize] == self.new[new_lo + y as usize]
+                {
+                    x += 1;
+                    y += 1;
+                }
+                let i = self.at(k);
+                self.vf[i] = x as usize;
+                if odd && (k - delta).abs() < d && x + self.vb[self.at(delta - k)] as isize >= n {
+                    return Some((old_lo + x0 as usize, new_lo + y0 as usize));
+                }
+            }
+            for k in (-d..=d).rev().step_by(2) {
+                let mut x =
+                    if k == -d || (k != d && self.vb[self.at(k - 1)] < self.vb[self.at(k + 1)]) {
+                        self.vb[self.at(k + 1)] as isize
+                    } else {
+                        self.vb[self.at(k - 1)] as isize + 1
+                    };
+                let mut y = x - k;
+                while x < n
+                    && y < m
+                    && self.old[old_lo + (n - x - 1) as usize]
+                        == self.new[new_lo + (m - y - 1) as usize]
+                {
+                    x += 1;
+                    y += 1;
+                }
+                let i = self.at(k);
+                self.vb[i] = x as usize;
+                if !odd && (k - delta).abs() <= d && x + self.vf[self.at(delta - k)] as isize >= n {
+                    return Some((old_lo + (n - x) as usize, new_lo + (m - y) as usize));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The language of a file as far as hunk headers are concerned.
+fn language_of(path: Option<&Path>) -> Option<&'static str> {
+    let ext = path?.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "rs" => "rust",
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => "javascript",
+        "py" => "python",
+        "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "go" | "kt" | "swift" => "c",
+        "ini" | "cfg" | "toml" | "conf" | "properties" => "ini",
+        "json" | "yaml" | "yml" => "data",
+        "md" | "markdown" => "markdown",
+        "xml" | "html" | "htm" | "xaml" | "csproj" => "xml",
+        "sh" | "bash" | "ps1" | "bat" | "cmd" => "shell",
+        _ => return None,
+    })
+}
+
+/// Whether `line` starts a function, section or heading, i.e. is worth
+/// showing as the context of a hunk below it.
+fn is_header_line(language: Option<&str>, line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let indented = trimmed.len() != line.len();
+    let starts_with_any = |words: &[&str]| words.iter().any(|w| trimmed.starts_with(w));
+    match language {
+        Some("rust") => starts_with_any(&[
+            "fn ",
+            "pub fn ",
+            "pub(crate) fn ",
+            "async fn ",
+            "pub async fn ",
+            "impl",
+            "struct ",
+            "pub struct ",
+            "enum ",
+            "pub enum ",
+            "trait ",
+            "pub trait ",
+            "mod ",
+            "pub mod ",
+        ]),
+        Some("javascript") => {
+            starts_with_any(&["function ", "async function ", "export ", "class "])
+                || (!indented && trimmed.starts_with("const ") && trimmed.contains("=>"))
+        }
+        Some("python") => starts_with_any(&["def ", "async def ", "class "]),
+        Some("c") => {
+            !indented
+                && !trimmed.starts_with(['#', '/', '*', '}'])
+                && (trimmed.contains('(') || trimmed.ends_with('{'))
+                && !trimmed.ends_with(';')
+        }
+        Some("ini") => trimmed.starts_with('['),
+        Some("data") => {
+            !indented
+                && (trimmed.ends_with(':') || trimmed.ends_with('{') || trimmed.ends_with('['))
+        }
+        Some("markdown") => trimmed.starts_with('#'),
+        Some("xml") => !indented && trimmed.starts_with('<') && !trimmed.starts_with("</"),
+        Some("shell") => {
+            trimmed.contains("()") || trimmed.starts_with("function ") || trimmed.starts_with(':')
+        }
+        // git's default: a line starting with a letter, `_` or `$`.
+        _ => line.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$'),
+    }
+}
+
+fn header_for(language: Option<&str>, lines: &[&str], before: usize) -> Option<String> {
+    let line = lines[..before.min(lines.len())]
+        .iter()
+        .rev()
+        .find(|line| is_header_line(language, line))?;
+    let line = line.trim();
+    Some(match line.char_indices().nth(MAX_HEADER_CHARS) {
+        Some((cut, _)) => format!("{}...", &line[..cut]),
+        None => line.to_string(),
+    })
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
+
+/// Pairs runs of removed lines with the added lines that follow them.
+fn side_by_side(lines: &[DiffLine]) -> Vec<SideBySideRow> {
+    let side = |line: &DiffLine, number: Option<usize>| SideLine {
+        number: number.unwrap_or(0),
+        text: line.text.clone(),
+    };
+    let mut rows = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].kind == LineKind::Context {
+            rows.push(SideBySideRow {
+                kind: LineKind::Context,
+                left: Some(side(&lines[i], lines[i].old_line)),
+                right: Some(side(&lines[i], lines[i].new_line)),
+            });
+            i += 1;
+            continue;
+        }
+        let removed_end = lines[i..]
+            .iter()
+            .position(|l| l.kind != LineKind::Removed)
+            .map_or(lines.len(), |p| i + p);
+        let added_end = lines[removed_end..]
+            .iter()
+            .position(|l| l.kind != LineKind::Added)
+            .map_or(lines.len(), |p| removed_end + p);
+        let removed = &lines[i..removed_end];
+        let added = &lines[removed_end..added_end];
+        for j in 0..removed.len().max(added.len()) {
+            let left = removed.get(j).map(|l| side(l, l.old_line));
+            let right = added.get(j).map(|l| side(l, l.new_line));
+            let kind = match (&left, &right) {
+                (Some(_), Some(_)) => LineKind::Changed,
+                (Some(_), None) => LineKind::Removed,
+                _ => LineKind::Added,
+            };
+            rows.push(SideBySideRow { kind, left, right });
+        }
+        i = added_end;
+    }
+    rows
+}
+
+/// Groups the edits into hunks with `context` unchanged lines around each
+/// change, merging hunks whose context would overlap.
+fn hunks(
+    edits: &[Edit],
+    old: &[&str],
+    new: &[&str],
+    context: usize,
+    language: Option<&str>,
+    layout: DiffLayout,
+) -> Vec<Hunk> {
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !matches!(e, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = i.saturating_add(context).saturating_add(1).min(edits.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // Lines of each file consumed before every edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Equal(..) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete(_) => old_pos += 1,
+            Edit::Insert(_) => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let lines: Vec<DiffLine> = edits[start..end]
+                .iter()
+                .map(|edit| match *edit {
+                    Edit::Equal(o, n) => DiffLine {
+                        kind: LineKind::Context,
+                        old_line: Some(o + 1),
+                        new_line: Some(n + 1),
+                        text: old[o].to_string(),
+                    },
+                    Edit::Delete(o) => DiffLine {
+                        kind: LineKind::Removed,
+                        old_line: Some(o + 1),
+                        new_line: None,
+                        text: old[o].to_string(),
+                    },
+                    Edit::Insert(n) => DiffLine {
+                        kind: LineKind::Added,
+                        old_line: None,
+                        new_line: Some(n + 1),
+                        text: new[n].to_string(),
+                    },
+                })
+                .collect();
+            let (old_before, new_before) = positions[start];
+            let (old_after, new_after) = positions[end];
+            let (old_lines, new_lines) = (old_after - old_before, new_after - new_before);
+            // Like diff(1), an empty side starts at the line before it.
+            let old_start = if old_lines == 0 {
+                old_before
+            } else {
+                old_before + 1
+            };
+            let new_start = if new_lines == 0 {
+                new_before
+            } else {
+                new_before + 1
+            };
+            let header = if old.is_empty() {
+                header_for(language, new, new_before)
+            } else {
+                header_for(language, old, old_before)
+            };
+            let (lines, rows) = match layout {
+                DiffLayout::Unified => (lines, Vec::new()),
+                DiffLayout::SideBySide => (Vec::new(), side_by_side(&lines)),
+            };
+            Hunk {
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                header,
+                lines,
+                rows,
+            }
+        })
+        .collect()
+}
+
+fn intern<'a>(ids: &mut HashMap<&'a str, u32>, lines: &[&'a str]) -> Vec<u32> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = ids.len() as u32;
+            *ids.entry(line).or_insert(next)
+        })
+        .collect()
+}
+
+/// Line diff of two texts.
+pub fn diff_text(
+    old: &str,
+    new: &str,
+    context: usize,
+    language: Option<&'static str>,
+    layout: DiffLayout,
+) -> (Vec<Hunk>, usize, usize, bool) {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    // Lines are compared as numbers, equal lines sharing one.
+    let mut ids = HashMap::new();
+    let old_ids = intern(&mut ids, &old_lines);
+    let new_ids = intern(&mut ids, &new_lines);
+
+    let (edits, approximate) = Myers::diff(&old_ids, &new_ids, Instant::now() + DIFF_TIMEOUT);
+    let added = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Insert(_)))
+        .count();
+    let removed = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Delete(_)))
+        .count();
+    let hunks = hunks(&edits, &old_lines, &new_lines, context, language, layout);
+    (hunks, added, removed, approximate)
+}
+
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_PROBE_BYTES)].contains(&0)
+}
+
+/// Diffs two files, either of which may be missing (`None`) to show a file
+/// that was added or removed. Binary files and files over the size limit
+/// are only compared for equality.
+pub fn diff_files(
+    old_path: Option<&Path>,
+    new_path: Option<&Path>,
+    context: usize,
+    layout: DiffLayout,
+) -> Result<FileDiff> {
+    let size = |path: Option<&Path>| -> Result<u64> {
+        Ok(match path {
+            Some(path) => fs::metadata(path)?.len(),
+            None => 0,
+        })
+    };
+    let (old_size, new_size) = (size(old_path)?, size(new_path)?);
+    let language = language_of(new_path.or(old_path));
+    let mut report = FileDiff {
+        old_path: old_path.map(|p| p.to_string_lossy().into_owned()),
+        new_path: new_path.map(|p| p.to_string_lossy().into_owned()),
+        kind: FileDiffKind::Text,
+        language,
+        identical: false,
+        old_size,
+        new_size,
+        added: 0,
+        removed: 0,
+        approximate: false,
+        hunks: Vec::new(),
+        config: None,
+    };
+
+    if old_size.max(new_size) > MAX_TEXT_BYTES {
+        report.kind = FileDiffKind::TooLarge;
+        report.identical = match (old_path, new_path) {
+            (Some(a), Some(b)) => {
+                old_size == new_size && fsutil::sha256_file(a)? == fsutil::sha256_file(b)?
+            }
+            _ => false,
+        };
+        return Ok(report);
+    }
+
+    let read = |path: Option<&Path>| -> Result<Vec<u8>> {
+        Ok(match path {
+            Some(path) => fs::read(path)?,
+            None => Vec::new(),
+        })
+    };
+    let (old, new) = (read(old_path)?, read(new_path)?);
+    report.identical = old_path.is_some() && new_path.is_some() && old == new;
+    if looks_binary(&old) || looks_binary(&new) {
+        report.kind = FileDiffKind::Binary;
+        return Ok(report);
+    }
+    if report.identical {
+        return Ok(report);
+    }
+
+    let (old, new) = (String::from_utf8_lossy(&old), String::from_utf8_lossy(&new));
+    // Source code such as `x = 1` can parse as a config file; only files
+    // that are config or of no known language are tried.
+    let maybe_config = language.is_none_or(|l| matches!(l, "ini" | "data"));
+    if let (Some(old_path), Some(new_path), true) = (old_path, new_path, maybe_config) {
+        report.config = configdiff::diff(old_path, &old, new_path, &new);
+    }
+    let (hunks, added, removed, approximate) = diff_text(&old, &new, context, language, layout);
+    report.hunks = hunks;
+    report.added = added;
+    report.removed = removed;
+    report.approximate = approximate;
+    Ok(report)
+}
+
+fn checked_file(scope: &Scope, path: Option<String>) -> Result<Option<PathBuf>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let path = scope.check(Path::new(&path))?;
+    if !path.is_file() {
+        return Err(Error::NotFound(path.display().to_string()));
+    }
+    Ok(Some(path))
+}
+
+/// Line diff of two files in the storage roots, usually the same relative
+/// path in two versions. Leave out one side to diff against nothing.
+#[tauri::command(async)]
+pub fn diff_file(
+    app: AppHandle,
+    old_path: Option<String>,
+    new_path: Option<String>,
+    layout: Option<DiffLayout>,
+    context: Option<usize>,
+) -> Result<FileDiff> {
+    if old_path.is_none() && new_path.is_none() {
+        return Err(Error::InvalidArgument("no files to compare".into()));
+    }
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    let old_path = checked_file(&scope, old_path)?;
+    let new_path = checked_file(&scope, new_path)?;
+    diff_files(
+        old_path.as_deref(),
+        new_path.as_deref(),
+        context.unwrap_or(DEFAULT_CONTEXT),
+        layout.unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    fn unified(old: &str, new: &str, context: usize) -> (Vec<Hunk>, usize, usize) {
+        let (hunks, added, removed, approximate) =
+            diff_text(old, new, context, None, DiffLayout::Unified);
+        assert!(!approximate);
+        (hunks, added, removed)
+    }
+
+    fn ranges(hunks: &[Hunk]) -> Vec<(usize, usize, usize, usize)> {
+        hunks
+            .iter()
+            .map(|h| (h.old_start, h.old_lines, h.new_start, h.new_lines))
+            .collect()
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        assert_eq!(ranges(&unified("a\nb\n", "a\nb\n", 3).0), []);
+        assert_eq!(ranges(&unified("a\r\nb", "a\nb\n", 3).0), []);
+    }
+
+    #[test]
+    fn surrounds_a_change_with_context() {
+        let old = numbered(10);
+        let new = old.replace("line 5\n", "line five\n");
+        let (hunks, added, removed) = unified(&old, &new, 2);
+        assert_eq!((added, removed), (1, 1));
+        assert_eq!(ranges(&hunks), [(3, 5, 3, 5)]);
+        let kinds: Vec<_> = hunks[0].lines.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                LineKind::Context,
+                LineKind::Context,
+                LineKind::Removed,
+                LineKind::Added,
+                LineKind::Context,
+                LineKind::Context
+            ]
+        );
+        assert_eq!(hunks[0].lines[2].old_line, Some(5));
+        assert_eq!(hunks[0].lines[3].new_line, Some(5));
+    }
+
+    #[test]
+    fn merges_hunks_whose_context_overlaps() {
+        let old = numbered(20);
+        let near = old.replace("line 5\n", "").replace("line 9\n", "");
+        assert_eq!(ranges(&unified(&old, &near, 2).0), [(3, 9, 3, 7)]);
+        let far = old.replace("line 3\n", "").replace("line 15\n", "");
+        assert_eq!(
+            ranges(&unified(&old, &far, 2).0),
+            [(1, 5, 1, 4), (13, 5, 12, 4)]
+        );
+    }
+
+    #[test]
+    fn any_context_size_is_accepted() {
+        let old = numbered(4);
+        let new = old.replace("line 2\n", "line two\n");
+        assert_eq!(ranges(&unified(&old, &new, usize::MAX).0), [(1, 4, 1, 4)]);
+    }
+
+    #[test]
+    fn empty_sides_start_at_the_line_before() {
+        assert_eq!(ranges(&unified("", "a\nb\n", 3).0), [(0, 0, 1, 2)]);
+        assert_eq!(ranges(&unified("a\nb\n", "", 3).0), [(1, 2, 0, 0)]);
+        let inserted = unified("a\nb\n", "a\nx\nb\n", 0).0;
+        assert_eq!(ranges(&inserted), [(1, 0, 2, 1)]);
+    }
+
+    #[test]
+    fn names_the_enclosing_function() {
+        let old = "fn first() {\n    1\n}\n\npub fn second() {\n    2\n    3\n}\n";
+        let new = old.replace("    3", "    4");
+        let (hunks, ..) = diff_text(old, &new, 1, Some("rust"), DiffLayout::Unified);
+        assert_eq!(hunks[0].header.as_deref(), Some("pub fn second() {"));
+        let (hunks, ..) = diff_text(old, &new, 1, Some("python"), DiffLayout::Unified);
+        assert_eq!(hunks[0].header, None);
+    }
+
+    #[test]
+    fn pairs_changed_lines_side_by_side() {
+        let (hunks, ..) = diff_text("a\nb\nc\n", "a\nB\nX\nc\n", 1, None, DiffLayout::SideBySide);
+        assert!(hunks[0].lines.is_empty());
+        let rows: Vec<_> = hunks[0]
+            .rows
+            .iter()
+            .map(|r| {
+                (
+                    r.kind,
+                    r.left.as_ref().map(|l| l.text.as_str()),
+                    r.right.as_ref().map(|r| r.text.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (LineKind::Context, Some("a"), Some("a")),
+                (LineKind::Changed, Some("b"), Some("B")),
+                (LineKind::Added, None, Some("X")),
+                (LineKind::Context, Some("c"), Some("c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_binary_content() {
+        assert!(looks_binary(b"MZ\0\x90"));
+        assert!(!looks_binary("plain text".as_bytes()));
+        let mut late = vec![b'a'; BINARY_PROBE_BYTES];
+        late.push(0);
+        assert!(!looks_binary(&late));
+    }
+}
@@ -0,0 +1,331 @@
+use crate::catalog;
+use filetime::FileTime;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Suffix of the temporary file a copy is written to before being renamed
+/// into place.
+const TEMP_SUFFIX: &str = ".pm-tmp";
+
+/// A regular file found by `walk_files`, relative to the walked directory.
+#[derive(Debug, Clone)]
+pub struct WalkedFile {
+    pub relative: PathBuf,
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// Recursively lists regular files under `dir`, sorted by relative path.
+/// Symlinks are not followed. With `skip_hidden_top`, dot-directories
+/// directly inside `dir` (trash, staging) are skipped, as they are for the
+/// storage root itself.
+pub fn walk_files(dir: &Path, skip_hidden_top: bool) -> io::Result<Vec<WalkedFile>> {
+    walk_top(
+        dir,
+        skip_hidden_top.then_some(catalog::is_hidden as fn(&str) -> bool),
+    )
+}
+
+/// Lists the files of a version: everything but its metadata directory.
+/// Other dot-files, such as `.env` or `.htaccess`, are content like any
+/// other file.
+pub fn walk_version(version_dir: &Path) -> io::Result<Vec<WalkedFile>> {
+    walk_top(version_dir, Some(|name| name == catalog::VERSION_META_DIR))
+}
+
+fn walk_top(dir: &Path, skip_top: Option<fn(&str) -> bool>) -> io::Result<Vec<WalkedFile>> {
+    let mut files = Vec::new();
+    walk_into(dir, Path::new(""), skip_top, &mut files)?;
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(files)
+}
+
+fn walk_into(
+    dir: &Path,
+    relative: &Path,
+    skip: Option<fn(&str) -> bool>,
+    files: &mut Vec<WalkedFile>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.is_some_and(|skip| skip(&name.to_string_lossy())) {
+            continue;
+        }
+        if is_temp_file(&entry.path()) {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(entry.path())?;
+        let child_relative = relative.join(&name);
+        if metadata.is_dir() {
+            walk_into(&entry.path(), &child_relative, None, files)?;
+        } else if metadata.is_file() {
+            files.push(WalkedFile {
+                relative: child_relative,
+                path: entry.path(),
+                metadata,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The temporary file `dest` is written to before being renamed into place.
+pub fn temp_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(TEMP_SUFFIX);
+    dest.with_file_name(name)
+}
+
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().ends_with(TEMP_SUFFIX))
+        .unwrap_or(false)
+}
+
+/// Seconds since the unix epoch, for timestamps in logs and metadata.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Nanoseconds since the unix epoch.
+pub fn mtime_nanos(meta: &Metadata) -> i64 {
+    let t = FileTime::from_last_modification_time(meta);
+    t.unix_seconds() * 1_000_000_000 + i64::from(t.nanoseconds())
+}
+
+/// FAT-formatted drives, common for mirrors, store mtimes with two-second
+/// granularity, so copies are compared with the same slack rsync uses.
+const MTIME_TOLERANCE_NANOS: i64 = 2_000_000_000;
+
+pub fn same_mtime(a: &Metadata, b: &Metadata) -> bool {
+    (mtime_nanos(a) - mtime_nanos(b)).abs() <= MTIME_TOLERANCE_NANOS
+}
+
+/// Relative path as a `/`-separated key, identical on every platform so
+/// state shared through the mirror stays comparable.
+pub fn relative_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Rewrites `key` for a rename of `from` to `to`, all `/`-separated
+/// relative keys. Keys outside `from` give `None`.
+pub fn rekey(key: &str, from: &str, to: &str) -> Option<String> {
+    if key == from {
+        return Some(to.to_string());
+    }
+    let rest = key.strip_prefix(from)?.strip_prefix('/')?;
+    Some(format!("{}/{}", to, rest))
+}
+
+/// Gives `path` its own copy of its data if other hard links share it, as
+/// they do after a hard-linked clone, so that changing its permissions
+/// cannot change theirs. Returns whether a link was broken. Windows does
+/// not report link counts, so nothing is done there.
+#[cfg(unix)]
+pub fn break_hard_link(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    if metadata.nlink() <= 1 {
+        return Ok(false);
+    }
+    let temp = temp_path(path);
+    let result = (|| {
+        fs::copy(path, &temp)?;
+        filetime::set_file_mtime(&temp, FileTime::from_last_modification_time(&metadata))?;
+        // A plain rename: making the destination writable first, as
+        // `replace_file` does, would change the other links too.
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.map(|_| true)
+}
+
+#[cfg(not(unix))]
+pub fn break_hard_link(_path: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Unix permission bits of a file. Windows only knows the read-only flag,
+/// which is mapped to `0o444`/`0o644` so manifests stay comparable across
+/// platforms.
+#[cfg(unix)]
+pub fn file_mode(meta: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(meta: &Metadata) -> u32 {
+    if meta.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+/// Clears all write bits, or restores the owner's write bit. Unlike
+/// `Permissions::set_readonly(false)` this never makes a file writable for
+/// everyone.
+#[cfg(unix)]
+pub fn set_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(path)?.permissions();
+    let mode = perms.mode();
+    perms.set_mode(if readonly {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    });
+    fs::set_permissions(path, perms)
+}
+
+#[cfg(not(unix))]
+pub fn set_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(readonly);
+    fs::set_permissions(path, perms)
+}
+
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Lowercase hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Copies `src` to `dest` through a temporary file in the destination
+/// directory, so `dest` is either the old file or the complete new one.
+/// The modification time and permissions of `src` are preserved.
+/// `on_progress` receives the number of bytes copied so far.
+pub fn copy_file_atomic<F>(src: &Path, dest: &Path, on_progress: F) -> io::Result<u64>
+where
+    F: FnMut(u64),
+{
+    copy_atomic_inner(src, dest, on_progress, false).map(|(copied, _)| copied)
+}
+
+/// Like `copy_file_atomic`, but hashes the data as it is read and reads the
+/// temporary file back before it is renamed into place. Returns the SHA-256
+/// of the copied content; a read-back mismatch fails with `InvalidData` and
+/// leaves `dest` untouched.
+pub fn copy_file_verified<F>(src: &Path, dest: &Path, on_progress: F) -> io::Result<String>
+where
+    F: FnMut(u64),
+{
+    copy_atomic_inner(src, dest, on_progress, true).map(|(_, hash)| hash.unwrap_or_default())
+}
+
+fn copy_atomic_inner<F>(
+    src: &Path,
+    dest: &Path,
+    mut on_progress: F,
+    verify: bool,
+) -> io::Result<(u64, Option<String>)>
+where
+    F: FnMut(u64),
+{
+    let parent = dest
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "destination has no parent"))?;
+    fs::create_dir_all(parent)?;
+    let temp = temp_path(dest);
+
+    let result = (|| {
+        let metadata = fs::metadata(src)?;
+        let mut reader = File::open(src)?;
+        let mut writer = File::create(&temp)?;
+        let mut hasher = verify.then(Sha256::new);
+        let mut buf = vec![0u8; 1024 * 1024];
+        let mut copied = 0u64;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n])?;
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&buf[..n]);
+            }
+            copied += n as u64;
+            on_progress(copied);
+        }
+        writer.sync_all()?;
+        drop(writer);
+
+        let hash = hasher.map(|h| hex::encode(h.finalize()));
+        if let Some(expected) = &hash {
+            if sha256_file(&temp)? != *expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "copied data does not match the source",
+                ));
+            }
+        }
+
+        filetime::set_file_mtime(&temp, FileTime::from_last_modification_time(&metadata))?;
+        fs::set_permissions(&temp, metadata.permissions())?;
+        replace_file(&temp, dest)?;
+        Ok((copied, hash))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Writes `bytes` to `dest` through a temporary file, like
+/// `copy_file_atomic`.
+pub fn write_file_atomic(dest: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = temp_path(dest);
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        drop(file);
+        replace_file(&temp, dest)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Renames `from` over `to`. Read-only destinations are made writable first
+/// because Windows refuses to replace them.
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Ok(meta) = fs::metadata(to) {
+        let mut perms = meta.permissions();
+        if perms.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            perms.set_readonly(false);
+            fs::set_permissions(to, perms)?;
+        }
+    }
+    fs::rename(from, to)
+}
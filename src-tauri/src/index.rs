@@ -0,0 +1,487 @@
+use crate::catalog::{self, Program, ProgramVersion};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, MAIN_SEPARATOR};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Manager, State};
+
+const INDEX_FILE: &str = "catalog.db";
+/// Emitted with the refreshed catalog when a background refresh found changes.
+pub const INDEX_UPDATED_EVENT: &str = "catalog-index-updated";
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Index(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Dir,
+    File,
+}
+
+impl EntryKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Dir => "dir",
+            EntryKind::File => "file",
+        }
+    }
+
+    fn parse(s: &str) -> EntryKind {
+        if s == "dir" {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Nanoseconds since the unix epoch.
+    pub mtime: i64,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshStats {
+    pub dirs_visited: usize,
+    pub dirs_reread: usize,
+    pub entries_written: usize,
+    pub entries_removed: usize,
+}
+
+impl RefreshStats {
+    pub fn changed(&self) -> bool {
+        self.entries_written > 0 || self.entries_removed > 0
+    }
+}
+
+/// On-disk index of everything under the storage root, kept in
+/// `<appDataDir>/catalog.db`.
+pub struct Index {
+    conn: Connection,
+}
+
+/// Managed state wrapper; the connection is not `Sync`.
+pub struct IndexState(pub Mutex<Index>);
+
+pub fn entry_from_metadata(path: &Path, meta: &fs::Metadata) -> IndexEntry {
+    let kind = if meta.is_dir() {
+        EntryKind::Dir
+    } else {
+        EntryKind::File
+    };
+    IndexEntry {
+        path: path.to_string_lossy().into_owned(),
+        kind,
+        size: if kind == EntryKind::File {
+            meta.len()
+        } else {
+            0
+        },
+        mtime: fsutil::mtime_nanos(meta),
+    }
+}
+
+fn row_to_entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<IndexEntry> {
+    Ok(IndexEntry {
+        path: row.get(0)?,
+        kind: EntryKind::parse(&row.get::<_, String>(1)?),
+        size: row.get::<_, i64>(2)? as u64,
+        mtime: row.get(3)?,
+    })
+}
+
+fn get(conn: &Connection, path: &str) -> Result<Option<IndexEntry>> {
+    Ok(conn
+        .prepare_cached("SELECT path, kind, size, mtime FROM entries WHERE path = ?1")?
+        .query_row(params![path], row_to_entry)
+        .optional()?)
+}
+
+fn children(conn: &Connection, parent: &str) -> Result<Vec<IndexEntry>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT path, kind, size, mtime FROM entries WHERE parent = ?1 ORDER BY path",
+    )?;
+    let rows = stmt.query_map(params![parent], row_to_entry)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+fn upsert(conn: &Connection, entry: &IndexEntry, parent: Option<&str>) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO entries (path, parent, kind, size, mtime)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(path) DO UPDATE SET
+             parent = excluded.parent, kind = excluded.kind,
+             size = excluded.size, mtime = excluded.mtime",
+    )?
+    .execute(params![
+        entry.path,
+        parent,
+        entry.kind.as_str(),
+        entry.size as i64,
+        entry.mtime
+    ])?;
+    Ok(())
+}
+
+fn remove_subtree(conn: &Connection, path: &str) -> Result<usize> {
+    let prefix = format!(
+        "{}{}",
+        path.trim_end_matches(MAIN_SEPARATOR),
+        MAIN_SEPARATOR
+    );
+    // Everything starting with `prefix` sorts below `prefix` with its last
+    // character bumped by one, so a range scan avoids LIKE escaping.
+    let mut upper = prefix.clone();
+    upper.pop();
+    upper.push(char::from_u32(MAIN_SEPARATOR as u32 + 1).unwrap_or(char::MAX));
+    Ok(conn.execute(
+        "DELETE FROM entries WHERE path = ?1 OR (path >= ?2 AND path < ?3)",
+        params![path, prefix, upper],
+    )?)
+}
+
+/// Re-reads `dir` if its mtime differs from the index, otherwise reuses the
+/// indexed children. Subdirectories are always descended into because a
+/// change deep in the tree does not touch the mtime of its ancestors.
+fn refresh_dir(
+    conn: &Connection,
+    dir: &Path,
+    meta: &fs::Metadata,
+    parent: Option<&str>,
+    stats: &mut RefreshStats,
+) -> Result<()> {
+    let key = dir.to_string_lossy().into_owned();
+    let current = entry_from_metadata(dir, meta);
+    stats.dirs_visited += 1;
+
+    let unchanged = matches!(
+        get(conn, &key)?,
+        Some(e) if e.kind == EntryKind::Dir && e.mtime == current.mtime
+    );
+    if unchanged {
+        for child in children(conn, &key)? {
+            if child.kind != EntryKind::Dir {
+                continue;
+            }
+            let child_path = Path::new(&child.path);
+            match fs::symlink_metadata(child_path) {
+                Ok(child_meta) if child_meta.is_dir() => {
+                    refresh_dir(conn, child_path, &child_meta, Some(&key), stats)?
+                }
+                // Gone without the parent mtime changing (e.g. clock skew on
+                // a network drive); drop it so the next listing re-adds it.
+                _ => stats.entries_removed += remove_subtree(conn, &child.path)?,
+            }
+        }
+        return Ok(());
+    }
+
+    stats.dirs_reread += 1;
+    upsert(conn, &current, parent)?;
+    stats.entries_written += 1;
+
+    let mut seen = HashMap::new();
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        // Internal directories at the top of the root (trash, staging) are
+        // not part of the catalog.
+        if parent.is_none() && catalog::is_hidden(&item.file_name().to_string_lossy()) {
+            continue;
+        }
+        let child_meta = match fs::symlink_metadata(item.path()) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        let entry = entry_from_metadata(&item.path(), &child_meta);
+        if entry.kind == EntryKind::File {
+            let previous = get(conn, &entry.path)?;
+            if matches!(&previous, Some(p) if p.kind == EntryKind::Dir) {
+                stats.entries_removed += remove_subtree(conn, &entry.path)?;
+            }
+            let same = matches!(
+                &previous,
+                Some(p) if p.kind == EntryKind::File && p.size == entry.size && p.mtime == entry.mtime
+            );
+            if !same {
+                upsert(conn, &entry, Some(&key))?;
+                stats.entries_written += 1;
+            }
+        }
+        seen.insert(entry.path, child_meta);
+    }
+
+    for child in children(conn, &key)? {
+        if !seen.contains_key(&child.path) {
+            stats.entries_removed += remove_subtree(conn, &child.path)?;
+        }
+    }
+
+    for (path, child_meta) in &seen {
+        if child_meta.is_dir() {
+            refresh_dir(conn, Path::new(path), child_meta, Some(&key), stats)?;
+        }
+    }
+    Ok(())
+}
+
+impl Index {
+    pub fn open(path: &Path) -> Result<Index> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS entries (
+                 path   TEXT PRIMARY KEY,
+                 parent TEXT,
+                 kind   TEXT NOT NULL,
+                 size   INTEGER NOT NULL,
+                 mtime  INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS entries_parent ON entries(parent);",
+        )?;
+        Ok(Index { conn })
+    }
+
+    pub fn get(&self, path: &str) -> Result<Option<IndexEntry>> {
+        get(&self.conn, path)
+    }
+
+    pub fn children(&self, parent: &str) -> Result<Vec<IndexEntry>> {
+        children(&self.conn, parent)
+    }
+
+    /// Brings the index for `root` up to date in a single transaction.
+    pub fn refresh(&mut self, root: &Path) -> Result<RefreshStats> {
+        fs::create_dir_all(root)?;
+        let meta = fs::metadata(root)?;
+        let mut stats = RefreshStats::default();
+        let tx = self.conn.transaction()?;
+        refresh_dir(&tx, root, &meta, None, &mut stats)?;
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Updates the index for a single path reported by the watcher. Files are
+    /// upserted even when their directory's mtime did not change, which a
+    /// plain `refresh` would miss.
+    pub fn sync_path(&mut self, root: &Path, path: &Path) -> Result<RefreshStats> {
+        let mut stats = RefreshStats::default();
+        let parent = match path.parent() {
+            Some(parent) if path != root && path.starts_with(root) => parent,
+            _ => return self.refresh(root),
+        };
+        let tx = self.conn.transaction()?;
+        match fs::symlink_metadata(path) {
+            Ok(meta) => {
+                // The parent may not be indexed yet, e.g. for a file in a
+                // directory created in the same debounce window.
+                if get(&tx, &parent.to_string_lossy())?.is_none() {
+                    drop(tx);
+                    return self.sync_path(root, parent);
+                }
+                let parent_key = parent.to_string_lossy();
+                if meta.is_dir() {
+                    refresh_dir(&tx, path, &meta, Some(&parent_key), &mut stats)?;
+                } else {
+                    stats.entries_removed += remove_subtree(&tx, &path.to_string_lossy())?;
+                    upsert(&tx, &entry_from_metadata(path, &meta), Some(&parent_key))?;
+                    stats.entries_written += 1;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                stats.entries_removed += remove_subtree(&tx, &path.to_string_lossy())?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Builds the Programs/Versions view of the catalog from the index alone.
+    /// Like `catalog::list_subdirs`, only visible directories count.
+    pub fn catalog(&self, root: &Path) -> Result<Vec<Program>> {
+        let listed =
+            |e: &IndexEntry| e.kind == EntryKind::Dir && !catalog::is_hidden(&file_name(&e.path));
+        let mut programs = Vec::new();
+        for program in self.children(&root.to_string_lossy())? {
+            if !listed(&program) {
+                continue;
+            }
+            let versions = self
+                .children(&program.path)?
+                .into_iter()
+                .filter(listed)
+                .map(|v| ProgramVersion {
+                    version: file_name(&v.path),
+                    path: v.path,
+                    modules: None,
+                    integrity: None,
+                    sealed: false,
+                    parent: None,
+                    scheme: None,
+                    prerelease: false,
+                })
+                .collect();
+            programs.push(Program::new(file_name(&program.path), versions));
+        }
+        Ok(programs)
+    }
+
+    pub fn is_empty_for(&self, root: &Path) -> Result<bool> {
+        Ok(self.get(&root.to_string_lossy())?.is_none())
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn open_for_app(app: &AppHandle) -> Result<Index> {
+    Index::open(&config::app_data_dir(app)?.join(INDEX_FILE))
+}
+
+impl IndexState {
+    pub fn lock(&self) -> Result<MutexGuard<'_, Index>> {
+        self.0
+            .lock()
+            .map_err(|_| Error::Index("index lock poisoned".into()))
+    }
+}
+
+fn refresh_catalog(app: &AppHandle) -> Result<Option<Vec<Program>>> {
+    let root = config::load(app)?.local_root();
+    let state = app.state::<IndexState>();
+    let mut index = state.lock()?;
+    if index.refresh(&root)?.changed() {
+        let mut programs = index.catalog(&root)?;
+        catalog::annotate(app, &mut programs);
+        Ok(Some(programs))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Refreshes the index in the background and emits the catalog if anything
+/// changed since the last refresh.
+pub fn spawn_refresh(app: AppHandle) {
+    std::thread::spawn(move || match refresh_catalog(&app) {
+        Ok(Some(programs)) => {
+            let _ = app.emit_all(INDEX_UPDATED_EVENT, programs);
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Error refreshing catalog index: {}", e),
+    });
+}
+
+/// Returns the catalog from the index. By default the indexed state is
+/// returned immediately and refreshed in the background; pass `refresh` to
+/// wait for the refresh instead, e.g. right after modifying the catalog.
+#[tauri::command(async)]
+pub fn load_catalog(
+    app: AppHandle,
+    state: State<'_, IndexState>,
+    refresh: Option<bool>,
+) -> Result<Vec<Program>> {
+    let root = config::load(&app)?.local_root();
+    let mut index = state.lock()?;
+    if refresh.unwrap_or(false) || index.is_empty_for(&root)? {
+        index.refresh(&root)?;
+        let mut programs = index.catalog(&root)?;
+        drop(index);
+        catalog::annotate(&app, &mut programs);
+        return Ok(programs);
+    }
+    let mut programs = index.catalog(&root)?;
+    drop(index);
+    catalog::annotate(&app, &mut programs);
+    spawn_refresh(app);
+    Ok(programs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    #[test]
+    fn catalog_lists_visible_directories_only() {
+        let dir = TempDir::new("index");
+        let root = dir.join("root");
+        for path in [
+            "App/1.0/app.exe",
+            "App/2.0/app.exe",
+            "App/.staging/move-1/content/x",
+            "App/.git/HEAD",
+            "App/readme.txt",
+            ".trash/App/0.9/app.exe",
+        ] {
+            testutil::write(&root.join(path), "x");
+        }
+        let mut index = Index::open(&dir.join("index.db")).unwrap();
+        index.refresh(&root).unwrap();
+
+        let programs = index.catalog(&root).unwrap();
+        let listed: Vec<_> = programs
+            .iter()
+            .map(|p| {
+                let versions: Vec<_> = p.versions.iter().map(|v| v.version.as_str()).collect();
+                (p.name.as_str(), versions)
+            })
+            .collect();
+        assert_eq!(listed, [("App", vec!["1.0", "2.0"])]);
+    }
+
+    #[test]
+    fn refresh_and_sync_path_follow_the_disk() {
+        let dir = TempDir::new("index-refresh");
+        let root = dir.join("root");
+        testutil::write(&root.join("App/1.0/app.exe"), "x");
+        testutil::write(&root.join("App/2.0/app.exe"), "x");
+        let mut index = Index::open(&dir.join("index.db")).unwrap();
+        assert!(index.refresh(&root).unwrap().changed());
+        assert!(!index.refresh(&root).unwrap().changed());
+
+        fs::remove_dir_all(root.join("App/1.0")).unwrap();
+        let stats = index.refresh(&root).unwrap();
+        assert_eq!(stats.entries_removed, 2);
+        let key = |path: &str| root.join(path).to_string_lossy().into_owned();
+        assert!(index.get(&key("App/1.0/app.exe")).unwrap().is_none());
+
+        // A new file in a new directory, as the watcher reports it.
+        testutil::write(&root.join("App/3.0/bin/app.exe"), "xyz");
+        index
+            .sync_path(&root, &root.join("App/3.0/bin/app.exe"))
+            .unwrap();
+        let added = index.get(&key("App/3.0/bin/app.exe")).unwrap().unwrap();
+        assert_eq!((added.kind, added.size), (EntryKind::File, 3));
+
+        fs::remove_dir_all(root.join("App/3.0")).unwrap();
+        index.sync_path(&root, &root.join("App/3.0")).unwrap();
+        assert!(index.get(&key("App/3.0/bin")).unwrap().is_none());
+        let versions: Vec<_> = index.catalog(&root).unwrap()[0]
+            .versions
+            .iter()
+            .map(|v| v.version.clone())
+            .collect();
+        assert_eq!(versions, ["2.0"]);
+    }
+}
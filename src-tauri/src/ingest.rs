@@ -0,0 +1,370 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::clone::{self, CloneMode};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::names;
+use crate::scope::Scope;
+use crate::staging::{self, Staging};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const DROP_EVENT: &str = "file-drop-received";
+pub const INGEST_PROGRESS_EVENT: &str = "drop-ingest-progress";
+
+/// The program selected in the UI, which dropped items become versions of.
+#[derive(Default)]
+pub struct DropTarget(Mutex<Option<String>>);
+
+impl DropTarget {
+    fn lock(&self) -> Result<MutexGuard<'_, Option<String>>> {
+        self.0
+            .lock()
+            .map_err(|_| Error::State("drop target lock poisoned".into()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DropKind {
+    Folder,
+    Archive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropCandidate {
+    pub path: String,
+    pub kind: DropKind,
+    pub suggested_version: String,
+}
+
+/// What the backend made of a drop, for the UI to confirm.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropPlan {
+    pub program: Option<String>,
+    pub candidates: Vec<DropCandidate>,
+    /// Dropped paths that are neither folders nor supported archives.
+    pub rejected: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestProgress {
+    pub program: String,
+    pub version: String,
+    pub source: String,
+    pub entry: String,
+    /// Bytes copied, or compressed bytes read for archives, out of `total`.
+    pub done: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestReport {
+    pub program: String,
+    pub version: String,
+    pub path: String,
+    pub kind: DropKind,
+    pub files: usize,
+    pub bytes: u64,
+    /// Symlinks that were not copied because they point outside the folder
+    /// or cannot be created on this platform.
+    pub skipped_links: Vec<String>,
+    pub duration_ms: u128,
+}
+
+fn kind_of(path: &Path) -> Option<DropKind> {
+    if path.is_dir() {
+        Some(DropKind::Folder)
+    } else if path.is_file() && ArchiveFormat::from_path(path).is_some() {
+        Some(DropKind::Archive)
+    } else {
+        None
+    }
+}
+
+/// Derives a version name from a dropped folder or archive: the archive
+/// extension and a leading program name (`app-1.2.zip` for `app`) are
+/// dropped, and a numeric suffix is added if the version already exists.
+pub fn suggest_version(program_dir: &Path, program: &str, source: &Path) -> String {
+    let name = match ArchiveFormat::stem(source) {
+        Some(stem) if source.is_file() => stem,
+        _ => source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let mut name = name.trim().trim_end_matches(['.', ' ']).to_string();
+    if name.len() > program.len()
+        && name.is_char_boundary(program.len())
+        && name[..program.len()].eq_ignore_ascii_case(program)
+    {
+        let rest = &name[program.len()..];
+        if rest.starts_with(['-', '_', ' ', '.']) {
+            let rest = rest[1..].trim();
+            if !rest.is_empty() {
+                name = rest.to_string();
+            }
+        }
+    }
+    if names::validate(&name).is_err() {
+        name = "new-version".to_string();
+    }
+
+    let mut suggestion = name.clone();
+    let mut n = 2;
+    while fs::symlink_metadata(program_dir.join(&suggestion)).is_ok() {
+        suggestion = format!("{}-{}", name, n);
+        n += 1;
+    }
+    suggestion
+}
+
+/// Classifies dropped paths and suggests version names for the program the
+/// UI has selected.
+pub fn plan(root: &Path, program: Option<String>, paths: &[PathBuf]) -> DropPlan {
+    let mut candidates = Vec::new();
+    let mut rejected = Vec::new();
+    for path in paths {
+        let display = path.to_string_lossy().into_owned();
+        let Some(kind) = kind_of(path) else {
+            rejected.push(display);
+            continue;
+        };
+        let suggested_version = match &program {
+            Some(program) => suggest_version(&root.join(program), program, path),
+            None => String::new(),
+        };
+        candidates.push(DropCandidate {
+            path: display,
+            kind,
+            suggested_version,
+        });
+    }
+    DropPlan {
+        program,
+        candidates,
+        rejected,
+    }
+}
+
+/// Window file-drop handler: the plan is sent to the UI, which confirms the
+/// version names and calls `ingest_dropped` for each candidate.
+pub fn handle_drop(app: &AppHandle, paths: &[PathBuf]) {
+    let program = match app.state::<DropTarget>().lock() {
+        Ok(program) => program.clone(),
+        Err(e) => {
+            eprintln!("Error reading drop target: {}", e);
+            return;
+        }
+    };
+    let root = match config::load(app) {
+        Ok(config) => config.local_root(),
+        Err(e) => {
+            eprintln!("Error loading config for file drop: {}", e);
+            return;
+        }
+    };
+    let _ = app.emit_all(DROP_EVENT, plan(&root, program, paths));
+}
+
+/// Copies a dropped folder into `<root>/<program>/<version>` through a
+/// staging directory, so the version only appears once the copy and its
+/// manifest are complete.
+pub fn ingest_folder<F>(
+    root: &Path,
+    scope: &Scope,
+    source: &Path,
+    program: &str,
+    version: &str,
+    mut on_progress: F,
+) -> Result<IngestReport>
+where
+    F: FnMut(&IngestProgress),
+{
+    let started = Instant::now();
+    let source = fs::canonicalize(source)?;
+    if root.starts_with(&source) {
+        return Err(Error::InvalidArgument(format!(
+            "{} contains the storage root",
+            source.display()
+        )));
+    }
+    staging::ensure_absent(root, program, version)?;
+
+    let staging = Staging::new(root, "drop")?;
+    let content = staging.content();
+    let copy = clone::copy_tree(&source, &content, CloneMode::Copy, |entry, done, total| {
+        on_progress(&IngestProgress {
+            program: program.to_string(),
+            version: version.to_string(),
+            source: source.to_string_lossy().into_owned(),
+            entry: entry.to_string(),
+            done,
+            total,
+        });
+    })?;
+    archive::check_manifest(&content, program, version)?;
+    let target = staging::install(root, scope, &content, program, version)?;
+    Ok(IngestReport {
+        program: program.to_string(),
+        version: version.to_string(),
+        path: target.to_string_lossy().into_owned(),
+        kind: DropKind::Folder,
+        files: copy.files,
+        bytes: copy.bytes,
+        skipped_links: copy.skipped_links,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+/// Imports a dropped archive as `<root>/<program>/<version>`, stripping a
+/// lone top-level folder.
+pub fn ingest_archive<F>(
+    root: &Path,
+    scope: &Scope,
+    source: &Path,
+    program: &str,
+    version: &str,
+    mut on_progress: F,
+) -> Result<IngestReport>
+where
+    F: FnMut(&IngestProgress),
+{
+    let source_display = source.to_string_lossy().into_owned();
+    let report = archive::import(root, scope, source, program, version, true, |progress| {
+        on_progress(&IngestProgress {
+            program: progress.program.clone(),
+            version: progress.version.clone(),
+            source: source_display.clone(),
+            entry: progress.entry.clone(),
+            done: progress.bytes_read,
+            total: progress.archive_size,
+        })
+    })?;
+    Ok(IngestReport {
+        program: report.program,
+        version: report.version,
+        path: report.path,
+        kind: DropKind::Archive,
+        files: report.files,
+        bytes: report.bytes,
+        skipped_links: report.skipped_links,
+        duration_ms: report.duration_ms,
+    })
+}
+
+/// Tells the backend which program dropped items should be added to.
+#[tauri::command]
+pub fn set_drop_target(app: AppHandle, program: Option<String>) -> Result<()> {
+    *app.state::<DropTarget>().lock()? = program;
+    Ok(())
+}
+
+/// Adds a dropped folder or archive as a new version. Archives with a
+/// single top-level folder have it stripped.
+#[tauri::command(async)]
+pub fn ingest_dropped(
+    app: AppHandle,
+    program: String,
+    source: String,
+    version: String,
+) -> Result<IngestReport> {
+    let config = config::load(&app)?;
+    let root = config.local_root();
+    fs::create_dir_all(&root)?;
+    let root = fs::canonicalize(&root)?;
+    let scope = Scope::from_config(&config)?;
+    let source = PathBuf::from(source);
+    let version = names::normalize(&version)?;
+    let emit = |progress: &IngestProgress| {
+        let _ = app.emit_all(INGEST_PROGRESS_EVENT, progress);
+    };
+
+    match kind_of(&source) {
+        Some(DropKind::Folder) => ingest_folder(&root, &scope, &source, &program, &version, emit),
+        Some(DropKind::Archive) => ingest_archive(&root, &scope, &source, &program, &version, emit),
+        None => Err(Error::InvalidArgument(format!(
+            "{} is neither a folder nor a supported archive",
+            source.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pm-ingest-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    /// A `.tar.gz` with `top/f` and the given symlinks.
+    fn tar_with_links(path: &Path, links: &[(&str, &str)]) {
+        let file = fs::File::create(path).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "top/f", &b"hello"[..])
+            .unwrap();
+        for (link, target) in links {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, link, target).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn ingest(dir: &Path, archive: &str) -> Result<IngestReport> {
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        let scope = Scope::from_config(&config::AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        ingest_archive(&root, &scope, &dir.join(archive), "App", "1.0", |_| {})
+    }
+
+    #[test]
+    fn dropped_archive_has_its_top_folder_stripped() {
+        let dir = temp_dir("strip");
+        tar_with_links(&dir.join("app.tar.gz"), &[("top/same", "f")]);
+        let report = ingest(&dir, "app.tar.gz").unwrap();
+        let version = dir.join("root/App/1.0");
+        assert_eq!(report.kind, DropKind::Archive);
+        assert_eq!(fs::read(version.join("f")).unwrap(), b"hello");
+        assert!(!version.join("top").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dropped_archive_with_an_escaping_link_is_refused() {
+        let dir = temp_dir("escape");
+        for (i, target) in ["..", "../top/.."].iter().enumerate() {
+            let archive = format!("app{}.tar.gz", i);
+            tar_with_links(&dir.join(&archive), &[("top/l", target)]);
+            match ingest(&dir, &archive) {
+                Err(Error::InvalidArgument(message)) => {
+                    assert!(message.contains("outside the version"), "{}", message)
+                }
+                other => panic!("{} was accepted: {:?}", target, other.map(|r| r.path)),
+            }
+            assert!(!dir.join("root/App/1.0").exists());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,16 +3,124 @@
     windows_subsystem = "windows"
 )]
 
-use std::fs;
+mod archive;
+mod bisync;
+mod catalog;
+mod clone;
+mod compare;
+mod config;
+mod configdiff;
+mod create;
+mod diff;
+mod error;
+mod export;
+mod filediff;
+mod fsutil;
+mod index;
+mod ingest;
+mod manifest;
+mod meta;
+mod names;
+mod query;
+mod rename;
+mod restore;
+mod scope;
+mod scrub;
+mod seal;
+mod staging;
+mod sync;
+#[cfg(test)]
+mod testutil;
+mod trash;
+mod tree;
+mod versioning;
+mod watcher;
 
-#[tauri::command]
-fn remove_dir_all(path: String) -> Result<(), String> {
-    fs::remove_dir_all(&path).map_err(|e| e.to_string())
-}
+use index::IndexState;
+use std::sync::Mutex;
+use tauri::{FileDropEvent, Manager, WindowEvent};
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![remove_dir_all])
+        .setup(|app| {
+            let index = index::open_for_app(&app.handle())?;
+            app.manage(IndexState(Mutex::new(index)));
+            app.manage(watcher::WatcherState::default());
+            app.manage(ingest::DropTarget::default());
+            if let Err(e) = watcher::start(&app.handle()) {
+                eprintln!("Error starting file watcher: {}", e);
+            }
+
+            scrub::start_scheduler(app.handle());
+
+            let handle = app.handle();
+            std::thread::spawn(move || {
+                let config = match config::load(&handle) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        eprintln!("Error loading config: {}", e);
+                        return;
+                    }
+                };
+                // Staging directories only outlive their operation if the
+                // app crashed or was killed half-way through it. Moves stage
+                // on the mirror too, when it is mounted.
+                let mirror = sync::mirror_roots(&config).ok().map(|(_, mirror)| mirror);
+                for root in std::iter::once(config.local_root()).chain(mirror) {
+                    if let Err(e) = staging::clean_abandoned(&root) {
+                        eprintln!("Error cleaning up staging directories: {}", e);
+                    }
+                }
+                if let Err(e) =
+                    trash::purge_expired(&config.local_root(), config.trash_retention_days)
+                {
+                    eprintln!("Error purging expired trash: {}", e);
+                }
+            });
+            Ok(())
+        })
+        .on_window_event(|event| {
+            if let WindowEvent::FileDrop(FileDropEvent::Dropped(paths)) = event.event() {
+                ingest::handle_drop(&event.window().app_handle(), paths);
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            catalog::scan_catalog,
+            index::load_catalog,
+            query::query_versions,
+            watcher::restart_watcher,
+            create::create_program,
+            create::create_version,
+            sync::sync_mirror,
+            bisync::list_sync_conflicts,
+            bisync::resolve_sync_conflict,
+            compare::compare_mirror,
+            compare::export_comparison,
+            diff::diff_versions,
+            filediff::diff_file,
+            restore::restore_mirror,
+            manifest::generate_manifest,
+            manifest::verify_version,
+            scrub::run_scrub,
+            scrub::scrub_history,
+            scrub::repair_version,
+            seal::seal_version,
+            seal::unseal_version,
+            seal::seal_log,
+            archive::import_archive,
+            export::export_archive,
+            ingest::set_drop_target,
+            ingest::ingest_dropped,
+            clone::clone_version,
+            rename::rename_program,
+            rename::move_version,
+            tree::list_directory,
+            trash::trash_version,
+            trash::list_trash,
+            trash::restore_trash,
+            trash::purge_trash,
+            trash::purge_expired_trash
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
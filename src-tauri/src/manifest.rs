@@ -0,0 +1,299 @@
+use crate::catalog::{self, VERSION_META_DIR};
+use crate::error::{Error, Result};
+use crate::fsutil::{self, WalkedFile};
+use crate::meta;
+use crate::scope;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Format 2 covers top-level dot-files of the version, which format 1
+/// manifests left out.
+const MANIFEST_FORMAT: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// `/`-separated path relative to the version directory.
+    pub path: String,
+    pub size: u64,
+    /// Unix permission bits; see `fsutil::file_mode`.
+    pub mode: u32,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format: u32,
+    pub program: String,
+    pub version: String,
+    pub created_at: u64,
+    /// Sorted by path.
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(program: &str, version: &str, files: Vec<ManifestEntry>) -> Manifest {
+        Manifest {
+            format: MANIFEST_FORMAT,
+            program: program.to_string(),
+            version: version.to_string(),
+            created_at: fsutil::now_secs(),
+            files,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifiedFile {
+    pub path: String,
+    pub expected: ManifestEntry,
+    pub actual: ManifestEntry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub program: String,
+    pub version: String,
+    pub manifest_created_at: u64,
+    pub files_checked: usize,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<ModifiedFile>,
+    /// Files that exist but could not be read.
+    pub unreadable: Vec<String>,
+    pub sealed: bool,
+    /// The manifest no longer matches the hash recorded when sealing.
+    pub seal_broken: bool,
+    /// Nothing was added, removed, modified or unreadable, and the seal
+    /// (if any) holds.
+    pub intact: bool,
+}
+
+pub fn manifest_path(version_dir: &Path) -> PathBuf {
+    version_dir.join(VERSION_META_DIR).join(MANIFEST_FILE)
+}
+
+fn entry_for(file: &WalkedFile) -> io::Result<ManifestEntry> {
+    Ok(ManifestEntry {
+        path: fsutil::relative_key(&file.relative),
+        size: file.metadata.len(),
+        mode: fsutil::file_mode(&file.metadata),
+        sha256: fsutil::sha256_file(&file.path)?,
+    })
+}
+
+/// Hashes every file in a version directory in parallel. The metadata
+/// directory, which holds the manifest itself, is not part of it.
+pub fn build(version_dir: &Path) -> Result<Vec<ManifestEntry>> {
+    let entries = fsutil::walk_version(version_dir)?
+        .par_iter()
+        .map(entry_for)
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+/// Builds and writes the manifest of `<root>/<program>/<version>`, replacing
+/// any previous one.
+pub fn generate(version_dir: &Path, program: &str, version: &str) -> Result<Manifest> {
+    let manifest = Manifest::new(program, version, build(version_dir)?);
+    let path = manifest_path(version_dir);
+    fs::create_dir_all(path.parent().unwrap_or(version_dir))?;
+    fsutil::write_file_atomic(&path, &serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+fn read_bytes(version_dir: &Path) -> Result<Vec<u8>> {
+    match fs::read(manifest_path(version_dir)) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(Error::NotFound(format!(
+            "{} has no manifest",
+            version_dir.display()
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Updates the program and version recorded in an existing manifest, e.g.
+/// after the version was imported or renamed, without re-hashing.
+pub fn relabel(version_dir: &Path, program: &str, version: &str) -> Result<()> {
+    let mut manifest: Manifest = serde_json::from_slice(&read_bytes(version_dir)?)?;
+    manifest.program = program.to_string();
+    manifest.version = version.to_string();
+    fsutil::write_file_atomic(
+        &manifest_path(version_dir),
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    Ok(())
+}
+
+/// SHA-256 of the manifest file itself, as recorded when sealing.
+pub fn manifest_hash(version_dir: &Path) -> Result<String> {
+    Ok(fsutil::sha256_bytes(&read_bytes(version_dir)?))
+}
+
+/// The entries of a version's manifest by path, for checking many of its
+/// files against one read of the manifest.
+pub fn load_entries(version_dir: &Path) -> Result<HashMap<String, ManifestEntry>> {
+    let manifest: Manifest = serde_json::from_slice(&read_bytes(version_dir)?)?;
+    Ok(manifest
+        .files
+        .into_iter()
+        .map(|e| (e.path.clone(), e))
+        .collect())
+}
+
+/// Whether `file` still has the size and content `recorded` for it.
+pub fn file_matches(file: &Path, recorded: &ManifestEntry) -> Result<bool> {
+    Ok(fs::metadata(file)?.len() == recorded.size && fsutil::sha256_file(file)? == recorded.sha256)
+}
+
+/// Re-hashes a version directory and compares it with its manifest. For
+/// sealed versions the manifest must also still be the one that was sealed.
+pub fn verify(version_dir: &Path) -> Result<VerifyReport> {
+    let bytes = read_bytes(version_dir)?;
+    let manifest: Manifest = serde_json::from_slice(&bytes)?;
+    let seal = meta::read(version_dir)?.seal;
+    let mut expected: BTreeMap<_, _> = manifest
+        .files
+        .into_iter()
+        .map(|e| (e.path.clone(), e))
+        .collect();
+
+    let mut files = fsutil::walk_version(version_dir)?;
+    if manifest.format < 2 {
+        // A relative path starts with the top-level name it lies in.
+        files.retain(|f| !catalog::is_hidden(&f.relative.to_string_lossy()));
+    }
+    let actual: Vec<_> = files
+        .par_iter()
+        .map(|f| (fsutil::relative_key(&f.relative), entry_for(f)))
+        .collect();
+
+    let mut report = VerifyReport {
+        program: manifest.program,
+        version: manifest.version,
+        manifest_created_at: manifest.created_at,
+        files_checked: actual.len(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        unreadable: Vec::new(),
+        sealed: seal.is_some(),
+        seal_broken: seal
+            .map(|s| s.manifest_sha256 != fsutil::sha256_bytes(&bytes))
+            .unwrap_or(false),
+        intact: false,
+    };
+    for (path, entry) in actual {
+        let Some(recorded) = expected.remove(&path) else {
+            report.added.push(path);
+            continue;
+        };
+        match entry {
+            Ok(actual) if actual == recorded => {}
+            Ok(actual) => report.modified.push(ModifiedFile {
+                path,
+                expected: recorded,
+                actual,
+            }),
+            Err(_) => report.unreadable.push(path),
+        }
+    }
+    report.removed = expected.into_keys().collect();
+    report.intact = !report.seal_broken
+        && report.added.is_empty()
+        && report.removed.is_empty()
+        && report.modified.is_empty()
+        && report.unreadable.is_empty();
+    Ok(report)
+}
+
+#[tauri::command(async)]
+pub fn generate_manifest(app: AppHandle, program: String, version: String) -> Result<Manifest> {
+    let dir = scope::local_version_dir(&app, &program, &version)?;
+    generate(&dir, &program, &version)
+}
+
+#[tauri::command(async)]
+pub fn verify_version(app: AppHandle, program: String, version: String) -> Result<VerifyReport> {
+    verify(&scope::local_version_dir(&app, &program, &version)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seal;
+    use crate::testutil::{self, TempDir};
+
+    fn version(dir: &TempDir) -> PathBuf {
+        let version = dir.join("app/1.0");
+        testutil::write(&version.join("app.bin"), "binary");
+        testutil::write(&version.join("docs/readme.txt"), "readme");
+        testutil::write(&version.join("docs/old.txt"), "old");
+        version
+    }
+
+    #[test]
+    fn a_fresh_manifest_verifies() {
+        let dir = TempDir::new("manifest");
+        let version = version(&dir);
+        let manifest = generate(&version, "app", "1.0").unwrap();
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["app.bin", "docs/old.txt", "docs/readme.txt"]);
+
+        let report = verify(&version).unwrap();
+        assert!(report.intact);
+        assert_eq!(report.files_checked, 3);
+        let entries = load_entries(&version).unwrap();
+        assert!(file_matches(&version.join("app.bin"), &entries["app.bin"]).unwrap());
+    }
+
+    #[test]
+    fn verify_reports_every_kind_of_change() {
+        let dir = TempDir::new("manifest-changes");
+        let version = version(&dir);
+        generate(&version, "app", "1.0").unwrap();
+        testutil::write(&version.join("app.bin"), "rebuilt");
+        testutil::write(&version.join("docs/new.txt"), "new");
+        fs::remove_file(version.join("docs/old.txt")).unwrap();
+
+        let report = verify(&version).unwrap();
+        assert!(!report.intact);
+        assert_eq!(report.added, ["docs/new.txt"]);
+        assert_eq!(report.removed, ["docs/old.txt"]);
+        assert_eq!(report.modified.len(), 1);
+        assert_eq!(report.modified[0].path, "app.bin");
+        assert_eq!(report.modified[0].actual.size, 7);
+    }
+
+    #[test]
+    fn a_replaced_manifest_breaks_the_seal() {
+        let dir = TempDir::new("manifest-seal");
+        let version = version(&dir);
+        seal::seal(&version, "app", "1.0").unwrap();
+        assert!(verify(&version).unwrap().intact);
+
+        fsutil::set_readonly(&manifest_path(&version), false).unwrap();
+        relabel(&version, "app", "1.0-renamed").unwrap();
+        let report = verify(&version).unwrap();
+        assert_eq!(report.version, "1.0-renamed");
+        assert!(report.sealed && report.seal_broken && !report.intact);
+    }
+
+    #[test]
+    fn a_missing_manifest_is_not_found() {
+        let dir = TempDir::new("manifest-missing");
+        let version = version(&dir);
+        assert!(matches!(verify(&version), Err(Error::NotFound(_))));
+        assert!(matches!(load_entries(&version), Err(Error::NotFound(_))));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::catalog::VERSION_META_DIR;
+use crate::error::Result;
+use crate::fsutil;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const VERSION_META_FILE: &str = "version.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Seal {
+    pub sealed_at: u64,
+    /// SHA-256 of the manifest file as written when the version was sealed.
+    pub manifest_sha256: String,
+}
+
+/// The version a clone was made from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParentVersion {
+    pub program: String,
+    pub version: String,
+    pub cloned_at: u64,
+}
+
+/// Per-version metadata stored next to the manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<Seal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ParentVersion>,
+}
+
+pub fn meta_path(version_dir: &Path) -> PathBuf {
+    version_dir.join(VERSION_META_DIR).join(VERSION_META_FILE)
+}
+
+/// Reads a version's metadata; versions without any have the default.
+pub fn read(version_dir: &Path) -> Result<VersionMeta> {
+    match fs::read(meta_path(version_dir)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(VersionMeta::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn write(version_dir: &Path, meta: &VersionMeta) -> Result<()> {
+    let path = meta_path(version_dir);
+    fs::create_dir_all(version_dir.join(VERSION_META_DIR))?;
+    fsutil::write_file_atomic(&path, &serde_json::to_vec_pretty(meta)?)?;
+    Ok(())
+}
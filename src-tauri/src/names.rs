@@ -0,0 +1,186 @@
+use crate::catalog;
+use crate::error::{Error, Result};
+use std::fs;
+use std::io;
+use std::path::Path;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Names that Windows maps to devices, with or without an extension. The
+/// superscript digits count as digits there too; they only ever match
+/// exactly, since the comparison ignores ASCII case alone.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3",
+    "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// Characters Windows does not allow in file names. The storage root may be
+/// mirrored to, or later moved to, a Windows machine, so they are refused on
+/// every platform.
+const WINDOWS_FORBIDDEN: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Longest name most filesystems accept, in bytes.
+const MAX_NAME_BYTES: usize = 255;
+
+fn invalid(message: String) -> Error {
+    Error::InvalidArgument(message)
+}
+
+/// Checks a program or version name the app is about to create. Names must
+/// be a single path component that is portable between Linux, macOS and
+/// Windows, visible in the catalog, and in Unicode NFC form. The error names
+/// the rule that was broken.
+pub fn validate(name: &str) -> Result<&str> {
+    if name.is_empty() {
+        return Err(invalid("a name cannot be empty".into()));
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '/' | '\\')) {
+        return Err(invalid(format!(
+            "{:?} contains the path separator {:?}",
+            name, c
+        )));
+    }
+    if name == "." || name == ".." {
+        return Err(invalid(format!(
+            "{:?} refers to a directory rather than naming one",
+            name
+        )));
+    }
+    if catalog::is_hidden(name) {
+        return Err(invalid(format!(
+            "{:?} starts with a dot and would be hidden",
+            name
+        )));
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Err(invalid(format!(
+            "{:?} contains the control character U+{:04X}",
+            name, c as u32
+        )));
+    }
+    if let Some(c) = name.chars().find(|c| WINDOWS_FORBIDDEN.contains(c)) {
+        return Err(invalid(format!(
+            "{:?} contains {:?}, which Windows does not allow in file names",
+            name, c
+        )));
+    }
+    if name.ends_with(['.', ' ']) {
+        return Err(invalid(format!(
+            "{:?} ends with a dot or space, which Windows silently drops",
+            name
+        )));
+    }
+    // `CON.txt` and `con .zip` open the device just like `CON` does.
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    if let Some(reserved) = RESERVED_NAMES.iter().find(|r| r.eq_ignore_ascii_case(stem)) {
+        return Err(invalid(format!(
+            "{:?} uses the reserved Windows device name {}",
+            name, reserved
+        )));
+    }
+    if name.len() > MAX_NAME_BYTES {
+        return Err(invalid(format!(
+            "{:?} is longer than {} bytes",
+            name, MAX_NAME_BYTES
+        )));
+    }
+    if !is_nfc(name) {
+        return Err(invalid(format!("{:?} is not in Unicode NFC form", name)));
+    }
+    Ok(name)
+}
+
+/// Brings a name entered by the user into NFC form and validates it.
+/// Commands call this on every name they are about to create, so the same
+/// text typed on different platforms always names the same directory.
+pub fn normalize(name: &str) -> Result<String> {
+    let name: String = name.nfc().collect();
+    validate(&name)?;
+    Ok(name)
+}
+
+/// Finds an entry of `dir` that is a different spelling of `name`: the same
+/// text in another Unicode normalisation form, such as a decomposed name
+/// created by an older macOS. Creating `name` next to it would show the
+/// same name twice.
+pub fn find_equivalent(dir: &Path, name: &str) -> Result<Option<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let wanted: String = name.nfc().collect();
+    for entry in entries {
+        let entry_name = entry?.file_name().to_string_lossy().into_owned();
+        if entry_name != name && entry_name.nfc().eq(wanted.chars()) {
+            return Ok(Some(entry_name));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(name: &str) -> bool {
+        validate(name).is_err()
+    }
+
+    #[test]
+    fn accepts_plain_names() {
+        for name in [
+            "Blender",
+            "2.0.1",
+            "v1.2-rc.1",
+            "My Tool",
+            "Café",
+            "COM10",
+            "conference",
+        ] {
+            assert_eq!(validate(name).ok(), Some(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_paths_and_hidden_names() {
+        for name in ["", ".", "..", "a/b", "a\\b", ".config", "tab\there"] {
+            assert!(rejected(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_what_windows_refuses() {
+        for name in ["a<b", "a:b", "what?", "star*", "trailing.", "trailing "] {
+            assert!(rejected(name), "{:?}", name);
+        }
+        assert!(rejected(&"x".repeat(MAX_NAME_BYTES + 1)));
+    }
+
+    #[test]
+    fn rejects_device_names() {
+        for name in [
+            "CON",
+            "con",
+            "nul.txt",
+            "Aux .zip",
+            "COM0",
+            "com9",
+            "LPT0",
+            "lpt1.log",
+            "CONIN$",
+            "conout$.txt",
+            "COM¹",
+            "com²",
+            "LPT³.bin",
+        ] {
+            assert!(rejected(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn requires_nfc() {
+        assert!(rejected("Cafe\u{301}"));
+        assert_eq!(normalize("Cafe\u{301}").unwrap(), "Caf\u{e9}");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::catalog::{self, ProgramVersion};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::index::IndexState;
+use crate::versioning;
+use semver::{Comparator, Op, Prerelease, Version, VersionReq};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionMatch {
+    pub program: String,
+    #[serde(flatten)]
+    pub version: ProgramVersion,
+}
+
+/// A SemVer range such as `^2.3`, `>1.8`, `>=1.2, <2` or `1.x || ^3`.
+pub struct VersionRange {
+    alternatives: Vec<VersionReq>,
+}
+
+impl VersionRange {
+    /// Parses a range in Cargo's syntax, with `||` between alternatives.
+    /// An empty range matches every version.
+    pub fn parse(text: &str) -> Result<VersionRange> {
+        let alternatives = text
+            .split("||")
+            .map(|part| {
+                let part = part.trim();
+                if part.is_empty() {
+                    return Ok(VersionReq::STAR);
+                }
+                VersionReq::parse(part).map_err(|e| {
+                    Error::InvalidArgument(format!("invalid version range {:?}: {}", part, e))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(VersionRange { alternatives })
+    }
+
+    /// Whether `version` lies in the range. Following SemVer, pre-releases
+    /// only match where the range names a pre-release of the same version,
+    /// e.g. `>=2.0.0-rc.1`, unless `include_prereleases` is set.
+    pub fn matches(&self, version: &Version, include_prereleases: bool) -> bool {
+        self.alternatives.iter().any(|req| {
+            if include_prereleases && !version.pre.is_empty() {
+                // Every pre-release of x.y.z is at least x.y.z-0, and a
+                // comparator with the same x.y.z and a pre-release opts it in.
+                let mut req = req.clone();
+                req.comparators.push(Comparator {
+                    op: Op::GreaterEq,
+                    major: version.major,
+                    minor: Some(version.minor),
+                    patch: Some(version.patch),
+                    pre: Prerelease::new("0").expect("valid pre-release"),
+                });
+                req.matches(version)
+            } else {
+                req.matches(version)
+            }
+        })
+    }
+}
+
+/// Versions of all programs, or only of `program`, that lie in `range`,
+/// oldest first within each program. Names that do not parse as a version
+/// never match.
+#[tauri::command(async)]
+pub fn query_versions(
+    app: AppHandle,
+    state: State<'_, IndexState>,
+    range: String,
+    program: Option<String>,
+    include_prereleases: Option<bool>,
+) -> Result<Vec<VersionMatch>> {
+    let range = VersionRange::parse(&range)?;
+    let include_prereleases = include_prereleases.unwrap_or(false);
+    let root = config::load(&app)?.local_root();
+    let mut index = state.lock()?;
+    if index.is_empty_for(&root)? {
+        index.refresh(&root)?;
+    }
+    let mut programs = index.catalog(&root)?;
+    drop(index);
+
+    programs.retain(|p| program.as_ref().is_none_or(|name| &p.name == name));
+    for program in &mut programs {
+        program.versions.retain(|v| {
+            versioning::parse(&v.version)
+                .and_then(|parsed| parsed.to_semver())
+                .is_some_and(|semver| range.matches(&semver, include_prereleases))
+        });
+    }
+    // Only the matches need their seal and integrity state.
+    catalog::annotate(&app, &mut programs);
+
+    Ok(programs
+        .into_iter()
+        .flat_map(|p| {
+            let name = p.name;
+            p.versions.into_iter().map(move |version| VersionMatch {
+                program: name.clone(),
+                version,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(range: &str, version: &str, include_prereleases: bool) -> bool {
+        let version = versioning::parse(version)
+            .and_then(|v| v.to_semver())
+            .expect("version parses");
+        VersionRange::parse(range)
+            .expect("range parses")
+            .matches(&version, include_prereleases)
+    }
+
+    #[test]
+    fn matches_caret_and_comparisons() {
+        assert!(matches("^2.3", "2.3.0", false));
+        assert!(matches("^2.3", "2.9.1", false));
+        assert!(!matches("^2.3", "3.0.0", false));
+        assert!(!matches("^2.3", "2.2.9", false));
+        // As in Cargo, `>1.8` lies above every 1.8.x.
+        assert!(matches(">1.8", "1.9", false));
+        assert!(!matches(">1.8", "1.8.1", false));
+        assert!(matches(">=1.2, <2", "1.9.9", false));
+        assert!(!matches(">=1.2, <2", "2.0", false));
+    }
+
+    #[test]
+    fn matches_any_alternative() {
+        assert!(matches("1.x || ^3", "1.4", false));
+        assert!(matches("1.x || ^3", "3.1", false));
+        assert!(!matches("1.x || ^3", "2.0", false));
+    }
+
+    #[test]
+    fn empty_range_matches_everything() {
+        assert!(matches("", "0.1", false));
+        assert!(matches("  ", "2024.3", false));
+        assert!(matches("|| ^9", "build-5", false));
+    }
+
+    #[test]
+    fn prereleases_need_opting_in() {
+        assert!(!matches("^2", "2.1.0-beta", false));
+        assert!(matches("^2", "2.1.0-beta", true));
+        assert!(matches(">=2.0.0-rc.1", "2.0.0-rc.2", false));
+        assert!(!matches("^2", "3.0.0-beta", true));
+    }
+
+    #[test]
+    fn matches_calver_and_file_versions() {
+        assert!(matches(">=2024.3", "2024.03.15", false));
+        assert!(!matches("<2024", "2024.01", false));
+        assert!(matches("=10.0.19041", "10.0.19041.1", false));
+        assert!(matches(">=1.2.3, <1.2.4", "1.2.3.9", false));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        assert!(matches!(
+            VersionRange::parse("^2 || >>1"),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
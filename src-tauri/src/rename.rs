@@ -0,0 +1,515 @@
+use crate::bisync;
+use crate::catalog;
+use crate::clone::{self, CloneMode};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::index::IndexState;
+use crate::manifest;
+use crate::meta;
+use crate::names;
+use crate::scope::{plain_name, Scope};
+use crate::scrub;
+use crate::staging::Staging;
+use crate::sync::{self, RunGuard};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveReport {
+    /// `program` or `program/version`, before and after.
+    pub from: String,
+    pub to: String,
+    pub path: String,
+    /// The move crossed filesystems and was done by copying.
+    pub copied: bool,
+    pub mirror_moved: bool,
+    /// Manifests rewritten with the new names, local and mirror.
+    pub relabeled: usize,
+    /// Clones whose recorded parent was updated.
+    pub children_updated: usize,
+    /// Set when a cross-device move could not delete the source afterwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leftover: Option<String>,
+}
+
+/// A program, or a version inside one.
+#[derive(Debug, Clone)]
+struct Item {
+    program: String,
+    version: Option<String>,
+}
+
+impl Item {
+    fn key(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}/{}", self.program, version),
+            None => self.program.clone(),
+        }
+    }
+
+    fn dir(&self, root: &Path) -> PathBuf {
+        let dir = root.join(&self.program);
+        match &self.version {
+            Some(version) => dir.join(version),
+            None => dir,
+        }
+    }
+}
+
+/// Whether `a` and `b` are the same directory entry, as with a case-only
+/// rename on a case-insensitive filesystem.
+#[cfg(unix)]
+fn same_entry(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_entry(a: &Path, b: &Path) -> bool {
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+/// The versions inside a moved item, with their names after the move.
+fn versions_of(root: &Path, item: &Item, to: &Item) -> Result<Vec<(PathBuf, String, String)>> {
+    let program_dir = root.join(&item.program);
+    let names = match &item.version {
+        Some(version) => vec![version.clone()],
+        None => catalog::list_subdirs(&program_dir)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect(),
+    };
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let new_name = to.version.clone().unwrap_or_else(|| name.clone());
+            (program_dir.join(&name), to.program.clone(), new_name)
+        })
+        .collect())
+}
+
+/// Relabeling a sealed version's manifest changes the hash its seal
+/// records, so the seal is checked first: a rename must not turn a broken
+/// seal into a valid one.
+fn check_seals(versions: &[(PathBuf, String, String)]) -> Result<()> {
+    for (dir, _, _) in versions {
+        if let Some(seal) = meta::read(dir)?.seal {
+            if manifest::manifest_hash(dir)? != seal.manifest_sha256 {
+                return Err(Error::Sealed(format!(
+                    "the seal of {} is broken; verify the version before moving it",
+                    dir.display()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the new names into a version's manifest and re-records the seal
+/// hash for sealed versions. Returns whether there was a manifest.
+fn relabel(dir: &Path, program: &str, version: &str) -> Result<bool> {
+    let manifest_path = manifest::manifest_path(dir);
+    if !manifest_path.exists() {
+        return Ok(false);
+    }
+    manifest::relabel(dir, program, version)?;
+    let mut meta = meta::read(dir)?;
+    if let Some(seal) = meta.seal.as_mut() {
+        fsutil::set_readonly(&manifest_path, true)?;
+        seal.manifest_sha256 = manifest::manifest_hash(dir)?;
+        meta::write(dir, &meta)?;
+    }
+    Ok(true)
+}
+
+/// Path, size and SHA-256 of every file below `dir`, hidden ones included.
+fn tree_digest(dir: &Path) -> Result<Vec<(PathBuf, u64, String)>> {
+    let digest = fsutil::walk_files(dir, false)?
+        .par_iter()
+        .map(|f| {
+            Ok((
+                f.relative.clone(),
+                f.metadata.len(),
+                fsutil::sha256_file(&f.path)?,
+            ))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(digest)
+}
+
+/// Moves `from` to `to`. On one filesystem this is a single rename. Across
+/// devices the tree is copied into staging next to `to`, verified against
+/// the source and renamed into place before the source is deleted.
+/// Returns whether it was copied and, if the source could not be deleted,
+/// why.
+fn move_dir(from: &Path, to: &Path) -> Result<(bool, Option<String>)> {
+    match fs::rename(from, to) {
+        Ok(()) => return Ok((false, None)),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let staging = Staging::beside(to, "move")?;
+    let content = staging.content();
+    clone::copy_tree(from, &content, CloneMode::Copy, |_, _, _| {})?;
+    if tree_digest(from)? != tree_digest(&content)? {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the copy of {} does not match the original", from.display()),
+        )));
+    }
+    fs::rename(&content, to)?;
+    drop(staging);
+
+    // Sealed files are read-only, which Windows refuses to delete.
+    let leftover = fsutil::walk_files(from, false)
+        .and_then(|files| {
+            files
+                .iter()
+                .try_for_each(|f| fsutil::set_readonly(&f.path, false))
+        })
+        .and_then(|_| fs::remove_dir_all(from))
+        .err()
+        .map(|e| format!("{}: {}", from.display(), e));
+    Ok((true, leftover))
+}
+
+/// Points clones of a moved version, or of any version of a moved program,
+/// at the parent's new name.
+fn update_children(root: &Path, from: &Item, to: &Item) -> Result<usize> {
+    let mut updated = 0;
+    for (_, program_dir) in catalog::list_subdirs(root)? {
+        for (_, version_dir) in catalog::list_subdirs(Path::new(&program_dir))? {
+            let dir = Path::new(&version_dir);
+            let mut meta = meta::read(dir)?;
+            let Some(parent) = meta.parent.as_mut() else {
+                continue;
+            };
+            if parent.program != from.program
+                || from.version.as_ref().is_some_and(|v| *v != parent.version)
+            {
+                continue;
+            }
+            parent.program = to.program.clone();
+            if let Some(version) = &to.version {
+                parent.version = version.clone();
+            }
+            meta::write(dir, &meta)?;
+            updated += 1;
+        }
+    }
+    Ok(updated)
+}
+
+struct Side {
+    root: PathBuf,
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// Checks that `from` exists and `to` is free below `root`.
+fn resolve(scope: &Scope, root: &Path, from: &Item, to: &Item) -> Result<Side> {
+    let from_dir = scope.check(&from.dir(root))?;
+    if !from_dir.is_dir() {
+        return Err(Error::NotFound(from.key()));
+    }
+    let to_dir = to.dir(root);
+    if fs::symlink_metadata(&to_dir).is_ok() && !same_entry(&from_dir, &to_dir) {
+        return Err(Error::AlreadyExists(to.key()));
+    }
+    // Renaming a differently normalised name to its NFC form is fine.
+    if let (Some(parent), Some(name)) = (to_dir.parent(), to_dir.file_name()) {
+        let name = name.to_string_lossy();
+        if let Some(existing) = names::find_equivalent(parent, &name)? {
+            if parent.join(&existing) != from.dir(root) {
+                return Err(Error::AlreadyExists(existing));
+            }
+        }
+    }
+    Ok(Side {
+        root: root.to_path_buf(),
+        from: from_dir,
+        to: to_dir,
+    })
+}
+
+fn perform(app: &AppHandle, from: Item, to: Item) -> Result<MoveReport> {
+    let _guard = RunGuard::acquire("a rename")?;
+    let config = config::load(app)?;
+    let scope = Scope::from_config(&config)?;
+    let local_root = config.local_root();
+    let local = resolve(&scope, &fs::canonicalize(&local_root)?, &from, &to)?;
+
+    // The mirror copy moves along, otherwise the next sync would bring the
+    // old name back. An unreachable mirror fails here, before anything moved.
+    let mirror_roots = if config.mirror_path.trim().is_empty() {
+        None
+    } else {
+        Some(sync::mirror_roots(&config)?)
+    };
+    let mirror = match &mirror_roots {
+        Some((_, mirror_root)) if from.dir(mirror_root).is_dir() => {
+            Some(resolve(&scope, mirror_root, &from, &to)?)
+        }
+        _ => None,
+    };
+
+    let mut sides = vec![&local];
+    sides.extend(mirror.as_ref());
+    let mut versions = Vec::new();
+    for side in &sides {
+        let found = versions_of(&side.root, &from, &to)?;
+        check_seals(&found)?;
+        versions.push(found);
+    }
+    // A version may move into a program that does not exist yet.
+    let mut created = Vec::new();
+    for side in &sides {
+        if let Some(parent) = side.to.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+                created.push(parent.to_path_buf());
+            }
+        }
+        scope.check(&side.to)?;
+    }
+
+    let moved = move_dir(&local.from, &local.to).and_then(|local_moved| {
+        if let Some(mirror) = &mirror {
+            if let Err(e) = move_dir(&mirror.from, &mirror.to) {
+                // Put the local copy back so both sides keep the old name.
+                if let Err(undo) = move_dir(&local.to, &local.from) {
+                    eprintln!("Error moving {:?} back: {}", local.to, undo);
+                }
+                return Err(e);
+            }
+        }
+        Ok(local_moved)
+    });
+    let (copied, leftover) = match moved {
+        Ok(moved) => moved,
+        Err(e) => {
+            for dir in created {
+                let _ = fs::remove_dir(dir);
+            }
+            return Err(e);
+        }
+    };
+
+    let mut relabeled = 0;
+    for (side, found) in sides.iter().zip(&versions) {
+        for (dir, program, version) in found {
+            let moved = match &from.version {
+                Some(_) => side.to.clone(),
+                None => side.to.join(dir.file_name().unwrap_or_default()),
+            };
+            if relabel(&moved, program, version)? {
+                relabeled += 1;
+            }
+        }
+    }
+
+    let (from_key, to_key) = (from.key(), to.key());
+    if let (Some((local_root, mirror_root)), Some(_)) = (&mirror_roots, &mirror) {
+        bisync::rename_keys(app, local_root, mirror_root, &from_key, &to_key)?;
+    }
+    scrub::rename_status(app, &from_key, &to_key)?;
+    let children_updated = update_children(&local.root, &from, &to)?;
+
+    let state = app.state::<IndexState>();
+    let mut index = state.lock()?;
+    for path in [from.dir(&local_root), to.dir(&local_root)] {
+        if let Err(e) = index.sync_path(&local_root, &path) {
+            eprintln!("Error updating the index for {:?}: {}", path, e);
+        }
+    }
+
+    Ok(MoveReport {
+        from: from_key,
+        to: to_key,
+        path: local.to.to_string_lossy().into_owned(),
+        copied,
+        mirror_moved: mirror.is_some(),
+        relabeled,
+        children_updated,
+        leftover,
+    })
+}
+
+#[tauri::command(async)]
+pub fn rename_program(app: AppHandle, program: String, new_name: String) -> Result<MoveReport> {
+    let from = Item {
+        program: plain_name(&program)?.to_string(),
+        version: None,
+    };
+    let to = Item {
+        program: names::normalize(&new_name)?,
+        version: None,
+    };
+    if from.program == to.program {
+        return Err(Error::InvalidArgument(format!(
+            "{:?} already has that name",
+            program
+        )));
+    }
+    perform(&app, from, to)
+}
+
+/// Renames a version, moves it to another program, or both.
+#[tauri::command(async)]
+pub fn move_version(
+    app: AppHandle,
+    program: String,
+    version: String,
+    to_program: String,
+    to_version: String,
+) -> Result<MoveReport> {
+    let from = Item {
+        program: plain_name(&program)?.to_string(),
+        version: Some(plain_name(&version)?.to_string()),
+    };
+    // The program name is kept as it is for a rename within the program, so
+    // versions of programs that predate the naming rules can be renamed.
+    let to_program = if to_program == program {
+        from.program.clone()
+    } else {
+        names::normalize(&to_program)?
+    };
+    let to = Item {
+        program: to_program,
+        version: Some(names::normalize(&to_version)?),
+    };
+    if from.key() == to.key() {
+        return Err(Error::InvalidArgument(format!(
+            "{} is already there",
+            from.key()
+        )));
+    }
+    perform(&app, from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::meta::{ParentVersion, VersionMeta};
+    use crate::seal;
+    use crate::testutil::{self, TempDir};
+
+    fn item(program: &str, version: Option<&str>) -> Item {
+        Item {
+            program: program.into(),
+            version: version.map(str::to_string),
+        }
+    }
+
+    fn local_root(dir: &TempDir) -> (PathBuf, Scope) {
+        let root = dir.join("local");
+        for version in ["1.0", "2.0"] {
+            let version_dir = root.join("app").join(version);
+            testutil::write(&version_dir.join("app.bin"), version);
+            manifest::generate(&version_dir, "app", version).unwrap();
+        }
+        let scope = Scope::from_config(&AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        (root, scope)
+    }
+
+    #[test]
+    fn targets_must_be_free_and_sources_present() {
+        let dir = TempDir::new("rename-resolve");
+        let (root, scope) = local_root(&dir);
+        let from = item("app", Some("1.0"));
+        assert!(matches!(
+            resolve(&scope, &root, &from, &item("app", Some("2.0"))),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            resolve(
+                &scope,
+                &root,
+                &item("app", Some("3.0")),
+                &item("app", Some("4.0"))
+            ),
+            Err(Error::NotFound(_))
+        ));
+        let side = resolve(&scope, &root, &from, &item("tool", Some("1.0"))).unwrap();
+        assert_eq!(side.to, root.join("tool/1.0"));
+    }
+
+    #[test]
+    fn a_moved_version_is_relabeled_and_keeps_its_seal() {
+        let dir = TempDir::new("rename-relabel");
+        let (root, _) = local_root(&dir);
+        seal::seal(&root.join("app/1.0"), "app", "1.0").unwrap();
+        let versions = versions_of(&root, &item("app", None), &item("tool", None)).unwrap();
+        assert_eq!(versions.len(), 2);
+        check_seals(&versions).unwrap();
+
+        assert_eq!(
+            move_dir(&root.join("app"), &root.join("tool")).unwrap(),
+            (false, None)
+        );
+        assert!(relabel(&root.join("tool/1.0"), "tool", "1.0").unwrap());
+        let report = manifest::verify(&root.join("tool/1.0")).unwrap();
+        assert_eq!(report.program, "tool");
+        assert!(report.sealed && report.intact);
+    }
+
+    #[test]
+    fn a_broken_seal_is_not_carried_along() {
+        let dir = TempDir::new("rename-broken-seal");
+        let (root, _) = local_root(&dir);
+        let version = root.join("app/1.0");
+        seal::seal(&version, "app", "1.0").unwrap();
+        fsutil::set_readonly(&manifest::manifest_path(&version), false).unwrap();
+        manifest::generate(&version, "app", "1.0-tampered").unwrap();
+
+        let versions =
+            versions_of(&root, &item("app", Some("1.0")), &item("app", Some("1.1"))).unwrap();
+        assert!(matches!(check_seals(&versions), Err(Error::Sealed(_))));
+    }
+
+    #[test]
+    fn clones_follow_their_parent() {
+        let dir = TempDir::new("rename-children");
+        let (root, _) = local_root(&dir);
+        let parent = |version: &str| VersionMeta {
+            parent: Some(ParentVersion {
+                program: "app".into(),
+                version: version.into(),
+                cloned_at: 0,
+            }),
+            ..Default::default()
+        };
+        meta::write(&root.join("app/2.0"), &parent("1.0")).unwrap();
+        testutil::write(&root.join("other/1.0/other.bin"), "other");
+        meta::write(&root.join("other/1.0"), &parent("0.9")).unwrap();
+
+        let updated =
+            update_children(&root, &item("app", Some("1.0")), &item("app", Some("1.5"))).unwrap();
+        assert_eq!(updated, 1);
+        let recorded = meta::read(&root.join("app/2.0")).unwrap().parent.unwrap();
+        assert_eq!(recorded.version, "1.5");
+
+        let updated = update_children(&root, &item("app", None), &item("tool", None)).unwrap();
+        assert_eq!(updated, 2);
+        let recorded = meta::read(&root.join("other/1.0")).unwrap().parent.unwrap();
+        assert_eq!(
+            (recorded.program.as_str(), recorded.version.as_str()),
+            ("tool", "0.9")
+        );
+    }
+}
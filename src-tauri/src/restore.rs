@@ -0,0 +1,493 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil::{self, WalkedFile};
+use crate::manifest;
+use crate::scope::plain_name;
+use crate::seal;
+use crate::staging::Staging;
+use crate::sync::{self, RunGuard};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const RESTORE_PROGRESS_EVENT: &str = "mirror-restore-progress";
+
+/// A program, or a single version of it, to restore.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreSelection {
+    pub program: String,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreOptions {
+    /// What to restore; the whole catalog when empty.
+    #[serde(default)]
+    pub selection: Vec<RestoreSelection>,
+    /// Replace local files that differ from the mirror. Without it such
+    /// files are left alone and reported as skipped.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreIssue {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub files_total: usize,
+    pub restored: usize,
+    pub restored_bytes: u64,
+    /// Already present locally with the same size and mtime.
+    pub unchanged: usize,
+    /// Present locally with different content and `overwrite` not set, or
+    /// part of a sealed version.
+    pub skipped: Vec<String>,
+    /// Selected programs/versions or files that could not be found on the
+    /// mirror.
+    pub missing: Vec<RestoreIssue>,
+    /// Files that could not be read from the mirror or failed verification,
+    /// and versions left out because they do not match their manifest.
+    pub corrupt: Vec<RestoreIssue>,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreProgress {
+    pub path: String,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_copied: u64,
+    pub file_size: u64,
+}
+
+fn selection_dirs(mirror: &Path, options: &RestoreOptions) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if options.selection.is_empty() {
+        return Ok(vec![(mirror.to_path_buf(), PathBuf::new())]);
+    }
+    options
+        .selection
+        .iter()
+        .map(|s| {
+            let mut relative = PathBuf::from(plain_name(&s.program)?);
+            if let Some(version) = &s.version {
+                relative.push(plain_name(version)?);
+            }
+            Ok((mirror.join(&relative), relative))
+        })
+        .collect()
+}
+
+/// What happens to one mirror file.
+enum Plan {
+    /// Already present locally with the same size and mtime.
+    Unchanged,
+    /// Differs locally and is kept; staged only so its version can be
+    /// verified.
+    Skip,
+    Copy,
+}
+
+fn plan(local: &Path, relative: &Path, file: &WalkedFile, options: &RestoreOptions) -> Plan {
+    let Ok(existing) = fs::metadata(local.join(relative)) else {
+        return Plan::Copy;
+    };
+    if existing.len() == file.metadata.len() && fsutil::same_mtime(&existing, &file.metadata) {
+        Plan::Unchanged
+    } else if !options.overwrite || seal::ensure_relative_unsealed(local, relative).is_err() {
+        Plan::Skip
+    } else {
+        Plan::Copy
+    }
+}
+
+/// `<program>/<version>` of a path below the mirror root, if it lies inside
+/// a version.
+fn version_of(relative: &Path) -> Option<PathBuf> {
+    let mut parts = relative.iter();
+    let version: PathBuf = [parts.next()?, parts.next()?].iter().collect();
+    parts.next().map(|_| version)
+}
+
+/// Puts an unchanged local file into a staged version: a hard link where
+/// the filesystem allows, a copy otherwise.
+fn stage_local(path: &Path, staged: &Path) -> io::Result<()> {
+    if let Some(parent) = staged.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::hard_link(path, staged)
+        .or_else(|_| fsutil::copy_file_verified(path, staged, |_| {}).map(|_| ()))
+}
+
+fn copy_issue(report: &mut RestoreReport, key: String, e: io::Error) {
+    let issue = RestoreIssue {
+        path: key,
+        reason: e.to_string(),
+    };
+    if e.kind() == io::ErrorKind::NotFound {
+        report.missing.push(issue);
+    } else {
+        report.corrupt.push(issue);
+    }
+}
+
+struct Restore<'a, F> {
+    local: &'a Path,
+    mirror: &'a Path,
+    options: &'a RestoreOptions,
+    report: RestoreReport,
+    files_done: usize,
+    files_total: usize,
+    on_progress: F,
+}
+
+impl<'a, F> Restore<'a, F>
+where
+    F: FnMut(&RestoreProgress),
+{
+    fn copy(&mut self, key: &str, src: &WalkedFile, dest: &Path) -> io::Result<String> {
+        let mut progress = RestoreProgress {
+            path: key.to_string(),
+            files_done: self.files_done,
+            files_total: self.files_total,
+            bytes_copied: 0,
+            file_size: src.metadata.len(),
+        };
+        let on_progress = &mut self.on_progress;
+        let result = fsutil::copy_file_verified(&src.path, dest, |copied| {
+            progress.bytes_copied = copied;
+            on_progress(&progress);
+        });
+        self.files_done += 1;
+        progress.files_done = self.files_done;
+        on_progress(&progress);
+        result
+    }
+
+    /// A file outside any version is copied straight into place.
+    fn restore_loose(&mut self, relative: &Path, file: &WalkedFile) {
+        let key = fsutil::relative_key(relative);
+        match plan(self.local, relative, file, self.options) {
+            Plan::Unchanged => {
+                self.report.unchanged += 1;
+                self.files_done += 1;
+            }
+            Plan::Skip => {
+                self.report.skipped.push(key);
+                self.files_done += 1;
+            }
+            Plan::Copy => match self.copy(&key, file, &self.local.join(relative)) {
+                Ok(_) => {
+                    self.report.restored += 1;
+                    self.report.restored_bytes += file.metadata.len();
+                }
+                Err(e) => copy_issue(&mut self.report, key, e),
+            },
+        }
+    }
+
+    /// Assembles the version in staging, from the mirror and from unchanged
+    /// local files, and checks it against its manifest. Only then are the
+    /// copied files moved into place; a version that fails is left alone and
+    /// reported as corrupt.
+    fn restore_version(&mut self, version: &Path, files: &[(PathBuf, WalkedFile)]) -> Result<()> {
+        let staging = Staging::new(self.local, "restore")?;
+        let content = staging.content();
+        // A local file is only "unchanged" by size and mtime; one that has
+        // rotted since is copied from the mirror like any other.
+        let recorded = manifest::load_entries(&self.mirror.join(version)).unwrap_or_default();
+        let mut install = Vec::new();
+        let mut skipped = Vec::new();
+        let mut failed = 0;
+        for (relative, file) in files {
+            let key = fsutil::relative_key(relative);
+            let dest = self.local.join(relative);
+            let inside = relative.strip_prefix(version).unwrap_or(relative);
+            let staged = content.join(inside);
+            let plan = match plan(self.local, relative, file, self.options) {
+                Plan::Unchanged => match recorded.get(&fsutil::relative_key(inside)) {
+                    Some(entry) if !manifest::file_matches(&dest, entry).unwrap_or(false) => {
+                        Plan::Copy
+                    }
+                    _ => Plan::Unchanged,
+                },
+                plan => plan,
+            };
+            let result = match plan {
+                Plan::Unchanged => {
+                    self.files_done += 1;
+                    stage_local(&dest, &staged)
+                }
+                Plan::Skip | Plan::Copy => self.copy(&key, file, &staged).map(|_| ()),
+            };
+            match (result, plan) {
+                (Err(e), _) => {
+                    copy_issue(&mut self.report, key, e);
+                    failed += 1;
+                }
+                (Ok(()), Plan::Unchanged) => {}
+                (Ok(()), Plan::Skip) => skipped.push(key),
+                (Ok(()), Plan::Copy) => install.push((staged, dest, file.metadata.len())),
+            }
+        }
+
+        let problem = if failed > 0 {
+            Some(format!("{} files could not be copied", failed))
+        } else {
+            match manifest::verify(&content) {
+                Ok(report) if report.intact => None,
+                Err(Error::NotFound(_)) => None,
+                Ok(report) => Some(format!(
+                    "does not match its manifest ({} added, {} removed, {} modified)",
+                    report.added.len(),
+                    report.removed.len(),
+                    report.modified.len() + report.unreadable.len() + report.seal_broken as usize
+                )),
+                Err(e) => Some(e.to_string()),
+            }
+        };
+        if let Some(reason) = problem {
+            self.report.corrupt.push(RestoreIssue {
+                path: fsutil::relative_key(version),
+                reason: format!("not restored: {}", reason),
+            });
+            return Ok(());
+        }
+
+        self.report.unchanged += files.len() - install.len() - skipped.len();
+        self.report.skipped.extend(skipped);
+        for (staged, dest, size) in install {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&staged, &dest)?;
+            self.report.restored += 1;
+            self.report.restored_bytes += size;
+        }
+        Ok(())
+    }
+}
+
+/// Rebuilds (part of) the local root from the mirror. Every copied file is
+/// hashed while it is read and verified after it is written, and each
+/// version must match its manifest before any of it is installed.
+pub fn restore_from_mirror<F>(
+    local: &Path,
+    mirror: &Path,
+    options: &RestoreOptions,
+    on_progress: F,
+) -> Result<RestoreReport>
+where
+    F: FnMut(&RestoreProgress),
+{
+    let started = Instant::now();
+    let mut report = RestoreReport::default();
+
+    let mut files = Vec::new();
+    for (dir, relative) in selection_dirs(mirror, options)? {
+        if !dir.is_dir() {
+            report.missing.push(RestoreIssue {
+                path: fsutil::relative_key(&relative),
+                reason: "not found on the mirror".into(),
+            });
+            continue;
+        }
+        match fsutil::walk_files(&dir, relative.as_os_str().is_empty()) {
+            Ok(found) => files.extend(found.into_iter().map(|f| (relative.join(&f.relative), f))),
+            Err(e) => report.corrupt.push(RestoreIssue {
+                path: fsutil::relative_key(&relative),
+                reason: e.to_string(),
+            }),
+        }
+    }
+    report.files_total = files.len();
+
+    let mut loose = Vec::new();
+    let mut versions: BTreeMap<PathBuf, Vec<_>> = BTreeMap::new();
+    for (relative, file) in files {
+        match version_of(&relative) {
+            Some(version) => versions.entry(version).or_default().push((relative, file)),
+            None => loose.push((relative, file)),
+        }
+    }
+
+    let mut restore = Restore {
+        local,
+        mirror,
+        options,
+        files_total: report.files_total,
+        report,
+        files_done: 0,
+        on_progress,
+    };
+    for (relative, file) in &loose {
+        restore.restore_loose(relative, file);
+    }
+    for (version, files) in &versions {
+        if let Err(e) = restore.restore_version(version, files) {
+            restore.report.corrupt.push(RestoreIssue {
+                path: fsutil::relative_key(version),
+                reason: format!("not restored: {}", e),
+            });
+        }
+    }
+
+    let mut report = restore.report;
+    report.duration_ms = started.elapsed().as_millis();
+    Ok(report)
+}
+
+#[tauri::command(async)]
+pub fn restore_mirror(app: AppHandle, options: Option<RestoreOptions>) -> Result<RestoreReport> {
+    let _guard = RunGuard::acquire("a restore")?;
+    let config = config::load(&app)?;
+    fs::create_dir_all(config.local_root())?;
+    let (local, mirror) = sync::mirror_roots(&config)?;
+    restore_from_mirror(&local, &mirror, &options.unwrap_or_default(), |progress| {
+        let _ = app.emit_all(RESTORE_PROGRESS_EVENT, progress);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+    use filetime::FileTime;
+
+    /// A mirror with two versions of `app`, each with a manifest.
+    fn mirror(dir: &TempDir) -> (PathBuf, PathBuf) {
+        let (local, mirror) = (dir.join("local"), dir.join("mirror"));
+        fs::create_dir_all(&local).unwrap();
+        for version in ["1.0", "2.0"] {
+            let version_dir = mirror.join("app").join(version);
+            testutil::write(&version_dir.join("app.bin"), version);
+            testutil::write(&version_dir.join("docs/readme.txt"), "readme");
+            manifest::generate(&version_dir, "app", version).unwrap();
+        }
+        testutil::write(&mirror.join("app/notes.txt"), "notes");
+        (local, mirror)
+    }
+
+    fn restore(local: &Path, mirror: &Path, options: &RestoreOptions) -> RestoreReport {
+        restore_from_mirror(local, mirror, options, |_| {}).unwrap()
+    }
+
+    #[test]
+    fn restores_everything_into_an_empty_root() {
+        let dir = TempDir::new("restore-all");
+        let (local, mirror) = mirror(&dir);
+        let report = restore(&local, &mirror, &RestoreOptions::default());
+        assert_eq!((report.files_total, report.restored), (7, 7));
+        assert!(report.corrupt.is_empty() && report.missing.is_empty());
+        assert_eq!(
+            testutil::read(&local.join("app/2.0/app.bin")).as_deref(),
+            Some("2.0")
+        );
+        assert_eq!(
+            testutil::read(&local.join("app/notes.txt")).as_deref(),
+            Some("notes")
+        );
+
+        let again = restore(&local, &mirror, &RestoreOptions::default());
+        assert_eq!((again.restored, again.unchanged), (0, 7));
+    }
+
+    #[test]
+    fn a_rotted_local_file_is_replaced_from_the_mirror() {
+        let dir = TempDir::new("restore-rot");
+        let (local, mirror) = mirror(&dir);
+        restore(&local, &mirror, &RestoreOptions::default());
+        // Same size and mtime, different content.
+        let rotted = local.join("app/1.0/app.bin");
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(&rotted).unwrap());
+        fs::write(&rotted, "X.0").unwrap();
+        filetime::set_file_mtime(&rotted, mtime).unwrap();
+
+        let report = restore(&local, &mirror, &RestoreOptions::default());
+        assert!(report.corrupt.is_empty());
+        assert_eq!((report.restored, report.unchanged), (1, 6));
+        assert_eq!(testutil::read(&rotted).as_deref(), Some("1.0"));
+    }
+
+    #[test]
+    fn a_corrupt_version_is_left_out_and_the_rest_restored() {
+        let dir = TempDir::new("restore-corrupt");
+        let (local, mirror) = mirror(&dir);
+        testutil::write(&mirror.join("app/1.0/app.bin"), "bitrot");
+
+        let report = restore(&local, &mirror, &RestoreOptions::default());
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.corrupt[0].path, "app/1.0");
+        assert!(!local.join("app/1.0").exists());
+        assert!(local.join("app/2.0/app.bin").exists());
+        assert!(local.join("app/notes.txt").exists());
+    }
+
+    #[test]
+    fn a_version_that_cannot_be_installed_does_not_stop_the_others() {
+        let dir = TempDir::new("restore-install");
+        let (local, mirror) = mirror(&dir);
+        // A directory where the version's file belongs.
+        fs::create_dir_all(local.join("app/1.0/app.bin")).unwrap();
+        let options = RestoreOptions {
+            overwrite: true,
+            ..Default::default()
+        };
+
+        let report = restore(&local, &mirror, &options);
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.corrupt[0].path, "app/1.0");
+        assert!(local.join("app/2.0/app.bin").exists());
+    }
+
+    #[test]
+    fn missing_selections_are_reported() {
+        let dir = TempDir::new("restore-missing");
+        let (local, mirror) = mirror(&dir);
+        let options = RestoreOptions {
+            selection: vec![
+                RestoreSelection {
+                    program: "app".into(),
+                    version: Some("2.0".into()),
+                },
+                RestoreSelection {
+                    program: "app".into(),
+                    version: Some("3.0".into()),
+                },
+            ],
+            overwrite: false,
+        };
+
+        let report = restore(&local, &mirror, &options);
+        assert_eq!(report.restored, 3);
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].path, "app/3.0");
+        assert!(!local.join("app/1.0").exists());
+        assert!(restore_from_mirror(
+            &local,
+            &mirror,
+            &RestoreOptions {
+                selection: vec![RestoreSelection {
+                    program: "../app".into(),
+                    version: None,
+                }],
+                overwrite: false,
+            },
+            |_| {}
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::config::{self, AppConfig};
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tauri::AppHandle;
+
+/// The set of directories destructive commands are allowed to touch: the
+/// configured `localPath` and `mirrorPath`, canonicalized.
+pub struct Scope {
+    roots: Vec<PathBuf>,
+}
+
+/// Accepts a name only if it is a single normal path component, i.e. it
+/// cannot climb out of or reach below the directory it is joined to.
+pub fn plain_name(name: &str) -> Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(Error::InvalidArgument(format!(
+            "{:?} is not a plain name",
+            name
+        ))),
+    }
+}
+
+impl Scope {
+    pub fn from_config(config: &AppConfig) -> Result<Scope> {
+        let mut roots = Vec::new();
+        for root in [&config.local_path, &config.mirror_path] {
+            if root.trim().is_empty() {
+                continue;
+            }
+            // A root that does not exist yet cannot contain anything to delete.
+            if let Ok(canonical) = fs::canonicalize(root) {
+                roots.push(canonical);
+            }
+        }
+        if roots.is_empty() {
+            return Err(Error::Config("no storage roots are configured".into()));
+        }
+        Ok(Scope { roots })
+    }
+
+    /// Resolves `path` and checks that it lies strictly inside one of the
+    /// roots. The parent is canonicalized so `..` and symlinked directories
+    /// are resolved; the final component is kept as-is so that operating on
+    /// the returned path affects a symlink itself rather than its target.
+    /// If the final component is a symlink, its target must be in scope too.
+    pub fn check(&self, path: &Path) -> Result<PathBuf> {
+        let display = path.display().to_string();
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::OutsideScope(display.clone()))?;
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => return Err(Error::OutsideScope(display)),
+        };
+        let parent = fs::canonicalize(parent)?;
+        let located = parent.join(name);
+
+        if self.roots.contains(&located) {
+            return Err(Error::ScopeRoot(display));
+        }
+        if !self.contains(&located) {
+            return Err(Error::OutsideScope(display));
+        }
+
+        if let Ok(meta) = fs::symlink_metadata(&located) {
+            if meta.file_type().is_symlink() {
+                let target = fs::canonicalize(&located)?;
+                if !self.contains(&target) || self.roots.contains(&target) {
+                    return Err(Error::OutsideScope(format!(
+                        "{} -> {}",
+                        display,
+                        target.display()
+                    )));
+                }
+            }
+        }
+        Ok(located)
+    }
+
+    /// Resolves `<root>/<program>/<version>` and checks it is in scope.
+    pub fn version_dir(&self, root: &Path, program: &str, version: &str) -> Result<PathBuf> {
+        let dir = root.join(plain_name(program)?).join(plain_name(version)?);
+        let dir = self.check(&dir)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("{}/{}", program, version)));
+        }
+        Ok(dir)
+    }
+
+    /// The root a checked path lies in.
+    pub fn root_of(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .map(PathBuf::as_path)
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.roots
+            .iter()
+            .any(|root| path.starts_with(root) && path != root)
+    }
+}
+
+/// Resolves `<program>/<version>` in the configured local root.
+pub fn local_version_dir(app: &AppHandle, program: &str, version: &str) -> Result<PathBuf> {
+    let config = config::load(app)?;
+    Scope::from_config(&config)?.version_dir(&config.local_root(), program, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    fn scope(dir: &TempDir) -> Scope {
+        testutil::write(&dir.join("local/app/1.0/app.bin"), "binary");
+        fs::create_dir_all(dir.join("mirror")).unwrap();
+        testutil::write(&dir.join("elsewhere/secret.txt"), "secret");
+        Scope::from_config(&AppConfig {
+            local_path: dir.join("local").to_string_lossy().into_owned(),
+            mirror_path: dir.join("mirror").to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn only_plain_names_are_accepted() {
+        assert!(plain_name("app").is_ok());
+        assert!(plain_name("1.0 beta").is_ok());
+        for name in ["", ".", "..", "a/b", "/abs", "../app"] {
+            assert!(plain_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn paths_must_lie_strictly_inside_a_root() {
+        let dir = TempDir::new("scope");
+        let scope = scope(&dir);
+        let version = scope.check(&dir.join("local/app/1.0")).unwrap();
+        assert_eq!(version, dir.join("local/app/1.0"));
+        assert!(scope.check(&dir.join("mirror/app")).is_ok());
+        assert_eq!(scope.root_of(&version), Some(dir.join("local").as_path()));
+
+        assert!(matches!(
+            scope.check(&dir.join("local")),
+            Err(Error::ScopeRoot(_))
+        ));
+        assert!(matches!(
+            scope.check(&dir.join("elsewhere/secret.txt")),
+            Err(Error::OutsideScope(_))
+        ));
+        assert!(matches!(
+            scope.check(&dir.join("local/app/../../elsewhere")),
+            Err(Error::OutsideScope(_))
+        ));
+        assert!(matches!(
+            scope.version_dir(&dir.join("local"), "app", "2.0"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_out_of_the_roots_are_refused() {
+        let dir = TempDir::new("scope-links");
+        let scope = scope(&dir);
+        let link = dir.join("local/app/escape");
+        std::os::unix::fs::symlink(dir.join("elsewhere"), &link).unwrap();
+        assert!(matches!(scope.check(&link), Err(Error::OutsideScope(_))));
+        assert!(matches!(
+            scope.check(&link.join("secret.txt")),
+            Err(Error::OutsideScope(_))
+        ));
+
+        let inside = dir.join("local/app/current");
+        std::os::unix::fs::symlink(dir.join("local/app/1.0"), &inside).unwrap();
+        assert_eq!(scope.check(&inside).unwrap(), inside);
+    }
+
+    #[test]
+    fn at_least_one_root_must_exist() {
+        let dir = TempDir::new("scope-roots");
+        let config = AppConfig {
+            local_path: dir.join("missing").to_string_lossy().into_owned(),
+            mirror_path: String::new(),
+            ..Default::default()
+        };
+        assert!(matches!(Scope::from_config(&config), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,600 @@
+use crate::catalog::{self, Program};
+use crate::config::{self, AppConfig};
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::manifest::{self, VerifyReport};
+use crate::scope::Scope;
+use crate::sync::{self, RunGuard};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+pub const SCRUB_PROGRESS_EVENT: &str = "scrub-progress";
+pub const SCRUB_FINISHED_EVENT: &str = "scrub-finished";
+
+const HISTORY_FILE: &str = "scrub-history.jsonl";
+const STATUS_FILE: &str = "scrub-status.json";
+/// Older runs are dropped from the history once it grows past this.
+const MAX_HISTORY_RUNS: usize = 200;
+/// How often the scheduler checks whether a scrub is due.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Serializes read-modify-write cycles of the status file.
+static STATUS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Location {
+    Local,
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Health {
+    Intact,
+    Corrupted,
+    /// No manifest has been generated, so the version cannot be checked.
+    NoManifest,
+}
+
+/// Result of checking one copy of a version against its manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionScrub {
+    pub program: String,
+    pub version: String,
+    pub location: Location,
+    pub health: Health,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modified: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unreadable: Vec<String>,
+    /// Set when the manifest itself could not be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One entry of the scrub history log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrubRun {
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub scheduled: bool,
+    pub versions_checked: usize,
+    pub corrupted: usize,
+    pub results: Vec<VersionScrub>,
+    /// Roots that were not checked, such as a mirror that is not mounted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedRoot>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrubProgress {
+    pub program: String,
+    pub version: String,
+    pub location: Location,
+    pub versions_done: usize,
+    pub versions_total: usize,
+}
+
+/// Latest known health of both copies of a version, shown in the catalog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionIntegrity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<Health>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<Health>,
+    pub checked_at: u64,
+}
+
+impl VersionIntegrity {
+    fn set(&mut self, location: Location, health: Health) {
+        match location {
+            Location::Local => self.local = Some(health),
+            Location::Mirror => self.mirror = Some(health),
+        }
+    }
+}
+
+type StatusMap = BTreeMap<String, VersionIntegrity>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairReport {
+    pub program: String,
+    pub version: String,
+    /// The copy that was repaired.
+    pub location: Location,
+    pub repaired: Vec<String>,
+    /// Files not in the manifest; they are left in place.
+    pub extra: Vec<String>,
+    pub intact: bool,
+}
+
+fn status_key(program: &str, version: &str) -> String {
+    format!("{}/{}", program, version)
+}
+
+fn check(program: &str, version: &str, location: Location, dir: &Path) -> VersionScrub {
+    let mut result = VersionScrub {
+        program: program.to_string(),
+        version: version.to_string(),
+        location,
+        health: Health::Intact,
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        unreadable: Vec::new(),
+        error: None,
+    };
+    match manifest::verify(dir) {
+        Ok(report) => {
+            if !report.intact {
+                result.health = Health::Corrupted;
+            }
+            result.added = report.added;
+            result.removed = report.removed;
+            result.modified = report.modified.into_iter().map(|m| m.path).collect();
+            result.unreadable = report.unreadable;
+        }
+        Err(Error::NotFound(_)) => result.health = Health::NoManifest,
+        Err(e) => {
+            result.health = Health::Corrupted;
+            result.error = Some(e.to_string());
+        }
+    }
+    result
+}
+
+/// Every `(program, version, dir)` under a storage root.
+fn list_versions(root: &Path) -> Result<Vec<(String, String, PathBuf)>> {
+    let mut versions = Vec::new();
+    for (program, program_path) in catalog::list_subdirs(root)? {
+        for (version, version_path) in catalog::list_subdirs(Path::new(&program_path))? {
+            versions.push((program.clone(), version, PathBuf::from(version_path)));
+        }
+    }
+    Ok(versions)
+}
+
+type Roots = Vec<(Location, PathBuf)>;
+
+/// A storage root a scrub could not check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedRoot {
+    pub location: Location,
+    pub reason: String,
+}
+
+/// The local root, and the mirror when one is configured and available. An
+/// unplugged mirror drive is skipped rather than failing the whole scrub.
+fn roots(config: &AppConfig) -> Result<(Roots, Vec<SkippedRoot>)> {
+    let mut roots = vec![(Location::Local, fs::canonicalize(config.local_root())?)];
+    let mut skipped = Vec::new();
+    if !config.mirror_path.trim().is_empty() {
+        match sync::mirror_roots(config) {
+            Ok((_, mirror)) => roots.push((Location::Mirror, mirror)),
+            Err(e) => skipped.push(SkippedRoot {
+                location: Location::Mirror,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    Ok((roots, skipped))
+}
+
+/// Re-hashes every version on every root against its manifest.
+pub fn scrub<F>(
+    roots: &[(Location, PathBuf)],
+    scheduled: bool,
+    mut on_progress: F,
+) -> Result<ScrubRun>
+where
+    F: FnMut(&ScrubProgress),
+{
+    let started_at = fsutil::now_secs();
+    let mut versions = Vec::new();
+    for (location, root) in roots {
+        versions.extend(
+            list_versions(root)?
+                .into_iter()
+                .map(|(program, version, dir)| (*location, program, version, dir)),
+        );
+    }
+
+    let mut results = Vec::new();
+    for (i, (location, program, version, dir)) in versions.iter().enumerate() {
+        on_progress(&ScrubProgress {
+            program: program.clone(),
+            version: version.clone(),
+            location: *location,
+            versions_done: i,
+            versions_total: versions.len(),
+        });
+        results.push(check(program, version, *location, dir));
+    }
+
+    Ok(ScrubRun {
+        started_at,
+        finished_at: fsutil::now_secs(),
+        scheduled,
+        versions_checked: results.len(),
+        corrupted: results
+            .iter()
+            .filter(|r| r.health == Health::Corrupted)
+            .count(),
+        results,
+        skipped: Vec::new(),
+    })
+}
+
+fn read_history(path: &Path) -> Result<Vec<ScrubRun>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    // A line cut short by a crash is skipped rather than losing the log.
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn append_history(path: &Path, run: &ScrubRun) -> Result<()> {
+    let mut line = serde_json::to_vec(run)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)?;
+
+    let runs = read_history(path)?;
+    if runs.len() > MAX_HISTORY_RUNS {
+        let mut text = Vec::new();
+        for run in &runs[runs.len() - MAX_HISTORY_RUNS..] {
+            text.extend(serde_json::to_vec(run)?);
+            text.push(b'\n');
+        }
+        fsutil::write_file_atomic(path, &text)?;
+    }
+    Ok(())
+}
+
+fn read_status(path: &Path) -> Result<StatusMap> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(StatusMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn update_status<F>(app: &AppHandle, update: F) -> Result<()>
+where
+    F: FnOnce(&mut StatusMap),
+{
+    let _lock = STATUS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = config::app_data_dir(app)?.join(STATUS_FILE);
+    let mut status = read_status(&path)?;
+    update(&mut status);
+    fsutil::write_file_atomic(&path, &serde_json::to_vec_pretty(&status)?)?;
+    Ok(())
+}
+
+/// Carries recorded scrub results over to a renamed program or version.
+pub fn rename_status(app: &AppHandle, from: &str, to: &str) -> Result<()> {
+    update_status(app, |status| {
+        *status = std::mem::take(status)
+            .into_iter()
+            .map(|(key, health)| (fsutil::rekey(&key, from, to).unwrap_or(key), health))
+            .collect();
+    })
+}
+
+/// Fills in `ProgramVersion::integrity` from the last scrub results.
+pub fn annotate(app: &AppHandle, programs: &mut [Program]) {
+    let status = config::app_data_dir(app).and_then(|dir| read_status(&dir.join(STATUS_FILE)));
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Error reading scrub status: {}", e);
+            return;
+        }
+    };
+    for program in programs {
+        for version in &mut program.versions {
+            version.integrity = status
+                .get(&status_key(&program.name, &version.version))
+                .cloned();
+        }
+    }
+}
+
+fn run(app: &AppHandle, scheduled: bool) -> Result<ScrubRun> {
+    // Not while a sync or repair may be rewriting the files being hashed.
+    let _guard = RunGuard::acquire("a scrub")?;
+    let config = config::load(app)?;
+    let (roots, skipped) = roots(&config)?;
+    let mut run = scrub(&roots, scheduled, |progress| {
+        let _ = app.emit_all(SCRUB_PROGRESS_EVENT, progress);
+    })?;
+    run.skipped = skipped;
+
+    let data_dir = config::app_data_dir(app)?;
+    fs::create_dir_all(&data_dir)?;
+    append_history(&data_dir.join(HISTORY_FILE), &run)?;
+    update_status(app, |status| {
+        // Versions that no longer exist drop out of the status, but a
+        // skipped root keeps the health it was last seen with.
+        let previous = std::mem::take(status);
+        for result in &run.results {
+            status
+                .entry(status_key(&result.program, &result.version))
+                .or_insert(VersionIntegrity {
+                    local: None,
+                    mirror: None,
+                    checked_at: run.finished_at,
+                })
+                .set(result.location, result.health);
+        }
+        for (key, old) in previous {
+            let Some(integrity) = status.get_mut(&key) else {
+                continue;
+            };
+            for skipped in &run.skipped {
+                let health = match skipped.location {
+                    Location::Local => old.local,
+                    Location::Mirror => old.mirror,
+                };
+                if let Some(health) = health {
+                    integrity.set(skipped.location, health);
+                }
+            }
+        }
+    })?;
+    let _ = app.emit_all(SCRUB_FINISHED_EVENT, &run);
+    Ok(run)
+}
+
+fn last_run_at(app: &AppHandle) -> Result<Option<u64>> {
+    let path = config::app_data_dir(app)?.join(HISTORY_FILE);
+    Ok(read_history(&path)?.last().map(|run| run.finished_at))
+}
+
+fn scrub_due(app: &AppHandle) -> Result<bool> {
+    let hours = config::load(app)?.scrub_interval_hours;
+    if hours == 0 {
+        return Ok(false);
+    }
+    Ok(match last_run_at(app)? {
+        Some(last) => fsutil::now_secs().saturating_sub(last) >= hours * 3600,
+        None => true,
+    })
+}
+
+/// Starts the background thread that scrubs whenever `scrubIntervalHours`
+/// have passed since the last run. The first check happens one interval
+/// after startup so launching the app never starts with a full re-hash.
+pub fn start_scheduler(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SCHEDULE_CHECK_INTERVAL);
+        let result = scrub_due(&app).and_then(|due| {
+            if due {
+                run(&app, true)?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) | Err(Error::Busy(_)) => {}
+            Err(e) => eprintln!("Error running scheduled scrub: {}", e),
+        }
+    });
+}
+
+/// Repairs the damaged copy of a version from the other one, which must
+/// verify clean. The healthy manifest is copied over first so the damaged
+/// copy is checked against a trusted list.
+pub fn repair(
+    healthy: &Path,
+    damaged: &Path,
+    healthy_report: &VerifyReport,
+) -> Result<(Vec<String>, VerifyReport)> {
+    if !healthy_report.intact {
+        return Err(Error::InvalidArgument(
+            "neither copy of the version verifies against its manifest".into(),
+        ));
+    }
+    fsutil::copy_file_atomic(
+        &manifest::manifest_path(healthy),
+        &manifest::manifest_path(damaged),
+        |_| {},
+    )?;
+    let report = manifest::verify(damaged)?;
+    let mut repaired = Vec::new();
+    for path in report
+        .removed
+        .iter()
+        .chain(report.modified.iter().map(|m| &m.path))
+        .chain(report.unreadable.iter())
+    {
+        fsutil::copy_file_verified(&healthy.join(path), &damaged.join(path), |_| {})?;
+        repaired.push(path.clone());
+    }
+    Ok((repaired, manifest::verify(damaged)?))
+}
+
+#[tauri::command(async)]
+pub fn run_scrub(app: AppHandle) -> Result<ScrubRun> {
+    run(&app, false)
+}
+
+/// The most recent scrub runs, newest first.
+#[tauri::command(async)]
+pub fn scrub_history(app: AppHandle, limit: Option<usize>) -> Result<Vec<ScrubRun>> {
+    let path = config::app_data_dir(&app)?.join(HISTORY_FILE);
+    let mut runs = read_history(&path)?;
+    runs.reverse();
+    runs.truncate(limit.unwrap_or(MAX_HISTORY_RUNS));
+    Ok(runs)
+}
+
+/// Verifies both copies of a version and rebuilds the damaged one from the
+/// healthy one.
+#[tauri::command(async)]
+pub fn repair_version(app: AppHandle, program: String, version: String) -> Result<RepairReport> {
+    let _guard = RunGuard::acquire("a version repair")?;
+    let config = config::load(&app)?;
+    let (local_root, mirror_root) = sync::mirror_roots(&config)?;
+    let scope = Scope::from_config(&config)?;
+    let local = scope.version_dir(&local_root, &program, &version)?;
+    let mirror = scope.version_dir(&mirror_root, &program, &version)?;
+
+    let local_ok = manifest::verify(&local).ok().filter(|r| r.intact);
+    let (location, (repaired, report)) = match local_ok {
+        Some(report) => (Location::Mirror, repair(&local, &mirror, &report)?),
+        None => (
+            Location::Local,
+            repair(&mirror, &local, &manifest::verify(&mirror)?)?,
+        ),
+    };
+
+    let health = if report.intact {
+        Health::Intact
+    } else {
+        Health::Corrupted
+    };
+    update_status(&app, |status| {
+        status
+            .entry(status_key(&program, &version))
+            .or_insert(VersionIntegrity {
+                local: None,
+                mirror: None,
+                checked_at: fsutil::now_secs(),
+            })
+            .set(location, health);
+    })?;
+
+    Ok(RepairReport {
+        program,
+        version,
+        location,
+        repaired,
+        extra: report.added,
+        intact: report.intact,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    /// `<dir>/<side>/App/1.0` with two files and a manifest.
+    fn version(dir: &TempDir, side: &str) -> PathBuf {
+        let version = dir.join(side).join("App/1.0");
+        testutil::write(&version.join("app.exe"), "binary");
+        testutil::write(&version.join("data/config.ini"), "[a]\nb = 1\n");
+        manifest::generate(&version, "App", "1.0").unwrap();
+        version
+    }
+
+    #[test]
+    fn an_unmounted_mirror_is_skipped() {
+        let dir = TempDir::new("scrub-roots");
+        fs::create_dir_all(dir.join("local")).unwrap();
+        let config = AppConfig {
+            local_path: dir.join("local").to_string_lossy().into_owned(),
+            mirror_path: dir.join("unplugged").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let (checked, skipped) = roots(&config).unwrap();
+        assert_eq!(checked, [(Location::Local, dir.join("local"))]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].location, Location::Mirror);
+        assert!(!dir.join("unplugged").exists());
+
+        let no_mirror = AppConfig {
+            mirror_path: String::new(),
+            ..config
+        };
+        assert!(roots(&no_mirror).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn scrub_waits_for_sync_and_the_other_way_round() {
+        let sync = RunGuard::acquire("a mirror sync").unwrap();
+        match RunGuard::acquire("a scrub") {
+            Err(Error::Busy(message)) => assert!(message.contains("a mirror sync")),
+            _ => panic!("scrub ran during a sync"),
+        }
+        drop(sync);
+        let scrub = RunGuard::acquire("a scrub").unwrap();
+        assert!(matches!(
+            RunGuard::acquire("a mirror sync"),
+            Err(Error::Busy(_))
+        ));
+        drop(scrub);
+    }
+
+    #[test]
+    fn scrub_finds_damage_and_repair_fixes_it() {
+        let dir = TempDir::new("scrub");
+        let local = version(&dir, "local");
+        let mirror = version(&dir, "mirror");
+        testutil::write(&mirror.join("app.exe"), "bitrot");
+        fs::remove_file(mirror.join("data/config.ini")).unwrap();
+        testutil::write(&mirror.join("extra.txt"), "mine");
+
+        let roots = vec![
+            (Location::Local, dir.join("local")),
+            (Location::Mirror, dir.join("mirror")),
+        ];
+        let run = scrub(&roots, false, |_| {}).unwrap();
+        assert_eq!(run.versions_checked, 2);
+        assert_eq!(run.corrupted, 1);
+        let damaged = run
+            .results
+            .iter()
+            .find(|r| r.location == Location::Mirror)
+            .unwrap();
+        assert_eq!(damaged.modified, ["app.exe"]);
+        assert_eq!(damaged.removed, ["data/config.ini"]);
+
+        let (repaired, report) =
+            repair(&local, &mirror, &manifest::verify(&local).unwrap()).unwrap();
+        assert_eq!(repaired.len(), 2);
+        assert_eq!(report.added, ["extra.txt"]);
+        assert_eq!(
+            testutil::read(&mirror.join("app.exe")).as_deref(),
+            Some("binary")
+        );
+        assert_eq!(
+            testutil::read(&mirror.join("extra.txt")).as_deref(),
+            Some("mine")
+        );
+
+        // Repairing from a copy that is itself damaged is refused.
+        testutil::write(&local.join("app.exe"), "rot");
+        let report = manifest::verify(&local).unwrap();
+        assert!(repair(&local, &mirror, &report).is_err());
+        assert_eq!(
+            testutil::read(&mirror.join("app.exe")).as_deref(),
+            Some("binary")
+        );
+    }
+}
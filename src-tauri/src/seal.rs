@@ -0,0 +1,389 @@
+use crate::catalog;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::manifest::{self, ManifestEntry};
+use crate::meta::{self, Seal};
+use crate::scope::{self, Scope};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri::AppHandle;
+
+const SEAL_LOG_FILE: &str = "seal-log.jsonl";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SealAction {
+    Seal,
+    Unseal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealLogEntry {
+    pub at: u64,
+    pub action: SealAction,
+    pub program: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+pub fn is_sealed(version_dir: &Path) -> Result<bool> {
+    Ok(meta::read(version_dir)?.seal.is_some())
+}
+
+/// Fails with `Error::Sealed` if `relative`, a path below `root`, is or lies
+/// inside a sealed version, or is a program containing one.
+pub fn ensure_relative_unsealed(root: &Path, relative: &Path) -> Result<()> {
+    let mut parts = relative.components();
+    let program = match parts.next() {
+        Some(Component::Normal(name)) if !catalog::is_hidden(&name.to_string_lossy()) => name,
+        _ => return Ok(()),
+    };
+    let program_dir = root.join(program);
+    let versions = match parts.next() {
+        Some(version) => vec![program_dir.join(version)],
+        None if program_dir.is_dir() => catalog::list_subdirs(&program_dir)?
+            .into_iter()
+            .map(|(_, path)| PathBuf::from(path))
+            .collect(),
+        None => Vec::new(),
+    };
+    for dir in versions {
+        if is_sealed(&dir)? {
+            let relative = dir.strip_prefix(root).unwrap_or(&dir);
+            return Err(Error::Sealed(fsutil::relative_key(relative)));
+        }
+    }
+    Ok(())
+}
+
+/// What `PushCheck` knows about a sealed version.
+struct SealedVersion {
+    /// The manifest is still the one that was sealed.
+    manifest_intact: bool,
+    files: HashMap<String, ManifestEntry>,
+}
+
+/// Checks files about to be copied from `root` over the mirror's copy. A
+/// file of a sealed version must still match the manifest it was sealed
+/// with, so a local copy that has rotted never replaces a healthy one. The
+/// seal and manifest of each version are read once per run.
+pub struct PushCheck {
+    root: PathBuf,
+    /// By `<program>/<version>`; `None` for versions that are not sealed.
+    versions: HashMap<PathBuf, Option<SealedVersion>>,
+}
+
+impl PushCheck {
+    pub fn new(root: &Path) -> PushCheck {
+        PushCheck {
+            root: root.to_path_buf(),
+            versions: HashMap::new(),
+        }
+    }
+
+    pub fn ensure_pushable(&mut self, relative: &Path) -> Result<()> {
+        let mut parts = relative.components();
+        let (Some(Component::Normal(program)), Some(Component::Normal(version))) =
+            (parts.next(), parts.next())
+        else {
+            return Ok(());
+        };
+        let version = Path::new(program).join(version);
+        if !self.versions.contains_key(&version) {
+            let sealed = self.load(&version)?;
+            self.versions.insert(version.clone(), sealed);
+        }
+        let Some(sealed) = &self.versions[&version] else {
+            return Ok(());
+        };
+        let intact = if parts.as_path().starts_with(catalog::VERSION_META_DIR) {
+            sealed.manifest_intact
+        } else {
+            match sealed.files.get(&fsutil::relative_key(parts.as_path())) {
+                Some(recorded) => manifest::file_matches(&self.root.join(relative), recorded)?,
+                None => false,
+            }
+        };
+        if !intact {
+            return Err(Error::Sealed(format!(
+                "{} does not match the manifest of its sealed version and was not copied",
+                fsutil::relative_key(relative)
+            )));
+        }
+        Ok(())
+    }
+
+    fn load(&self, version: &Path) -> Result<Option<SealedVersion>> {
+        let dir = self.root.join(version);
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let Some(seal) = meta::read(&dir)?.seal else {
+            return Ok(None);
+        };
+        Ok(Some(SealedVersion {
+            manifest_intact: manifest::manifest_hash(&dir)? == seal.manifest_sha256,
+            files: manifest::load_entries(&dir)?,
+        }))
+    }
+}
+
+/// Like `ensure_relative_unsealed` for a path already checked by `scope`.
+pub fn ensure_unsealed(scope: &Scope, path: &Path) -> Result<()> {
+    match scope.root_of(path) {
+        Some(root) => ensure_relative_unsealed(root, path.strip_prefix(root).unwrap_or(path)),
+        None => Ok(()),
+    }
+}
+
+/// Makes every file of a version read-only, writes a fresh manifest and
+/// records its hash in the version metadata.
+pub fn seal(version_dir: &Path, program: &str, version: &str) -> Result<Seal> {
+    let mut meta = meta::read(version_dir)?;
+    if meta.seal.is_some() {
+        return Err(Error::Sealed(format!(
+            "{}/{} is already sealed",
+            program, version
+        )));
+    }
+    let manifest_path = manifest::manifest_path(version_dir);
+    let previous_manifest = match fs::read(&manifest_path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut made_readonly = Vec::new();
+    let result = (|| {
+        // Permissions first, so the manifest records the sealed modes. Files
+        // shared with a hard-linked clone get their own copy, or unsealing
+        // the clone would make them writable again.
+        for file in fsutil::walk_version(version_dir)? {
+            fsutil::break_hard_link(&file.path)?;
+            if !file.metadata.permissions().readonly() {
+                fsutil::set_readonly(&file.path, true)?;
+                made_readonly.push(file.path);
+            }
+        }
+        manifest::generate(version_dir, program, version)?;
+        fsutil::set_readonly(&manifest_path, true)?;
+        made_readonly.push(manifest_path.clone());
+
+        let seal = Seal {
+            sealed_at: fsutil::now_secs(),
+            manifest_sha256: manifest::manifest_hash(version_dir)?,
+        };
+        meta.seal = Some(seal.clone());
+        meta::write(version_dir, &meta)?;
+        Ok(seal)
+    })();
+
+    // Half a seal is worse than none: put back the permissions and the
+    // manifest, so the version is left as it was.
+    if result.is_err() {
+        for path in &made_readonly {
+            if let Err(e) = fsutil::set_readonly(path, false) {
+                eprintln!("Error restoring permissions of {:?}: {}", path, e);
+            }
+        }
+        let restored = match &previous_manifest {
+            Some(bytes) => fsutil::write_file_atomic(&manifest_path, bytes),
+            None => fs::remove_file(&manifest_path).or_else(|e| match e.kind() {
+                io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        };
+        if let Err(e) = restored {
+            eprintln!("Error restoring manifest of {:?}: {}", version_dir, e);
+        }
+    }
+    result
+}
+
+/// Reverses `seal`: files become writable again and the manifest is
+/// regenerated to match.
+pub fn unseal(version_dir: &Path, program: &str, version: &str) -> Result<()> {
+    let mut meta = meta::read(version_dir)?;
+    if meta.seal.is_none() {
+        return Err(Error::InvalidArgument(format!(
+            "{}/{} is not sealed",
+            program, version
+        )));
+    }
+    // A file still shared with another version may be sealed there.
+    for file in fsutil::walk_version(version_dir)? {
+        fsutil::break_hard_link(&file.path)?;
+        fsutil::set_readonly(&file.path, false)?;
+    }
+    let manifest_path = manifest::manifest_path(version_dir);
+    if manifest_path.exists() {
+        fsutil::set_readonly(&manifest_path, false)?;
+    }
+    meta.seal = None;
+    meta::write(version_dir, &meta)?;
+    manifest::generate(version_dir, program, version)?;
+    Ok(())
+}
+
+fn log_path(app: &AppHandle) -> Result<PathBuf> {
+    Ok(config::app_data_dir(app)?.join(SEAL_LOG_FILE))
+}
+
+fn append_log(app: &AppHandle, entry: &SealLogEntry) -> Result<()> {
+    let path = log_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn seal_version(app: AppHandle, program: String, version: String) -> Result<Seal> {
+    let dir = scope::local_version_dir(&app, &program, &version)?;
+    let seal = seal(&dir, &program, &version)?;
+    append_log(
+        &app,
+        &SealLogEntry {
+            at: seal.sealed_at,
+            action: SealAction::Seal,
+            program,
+            version,
+            reason: None,
+        },
+    )?;
+    Ok(seal)
+}
+
+/// Unsealing is always recorded in the seal log, with the given reason.
+#[tauri::command(async)]
+pub fn unseal_version(
+    app: AppHandle,
+    program: String,
+    version: String,
+    reason: Option<String>,
+) -> Result<()> {
+    let dir = scope::local_version_dir(&app, &program, &version)?;
+    unseal(&dir, &program, &version)?;
+    append_log(
+        &app,
+        &SealLogEntry {
+            at: fsutil::now_secs(),
+            action: SealAction::Unseal,
+            program,
+            version,
+            reason: reason.filter(|r| !r.trim().is_empty()),
+        },
+    )
+}
+
+/// Seal and unseal events, newest first.
+#[tauri::command]
+pub fn seal_log(app: AppHandle, limit: Option<usize>) -> Result<Vec<SealLogEntry>> {
+    let text = match fs::read_to_string(log_path(&app)?) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries: Vec<SealLogEntry> = text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    entries.reverse();
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    fn version(dir: &TempDir) -> PathBuf {
+        let dir = dir.join("local/app/1.0");
+        testutil::write(&dir.join("app.bin"), "binary");
+        testutil::write(&dir.join("docs/readme.txt"), "readme");
+        dir
+    }
+
+    fn is_readonly(path: &Path) -> bool {
+        fs::metadata(path).unwrap().permissions().readonly()
+    }
+
+    #[test]
+    fn sealing_makes_files_readonly_until_unsealed() {
+        let root = TempDir::new("seal-roundtrip");
+        let dir = version(&root);
+        seal(&dir, "app", "1.0").unwrap();
+        assert!(is_readonly(&dir.join("app.bin")));
+        assert!(is_readonly(&dir.join("docs/readme.txt")));
+        assert!(is_readonly(&manifest::manifest_path(&dir)));
+        assert!(seal(&dir, "app", "1.0").is_err());
+
+        unseal(&dir, "app", "1.0").unwrap();
+        assert!(!is_readonly(&dir.join("app.bin")));
+        assert!(meta::read(&dir).unwrap().seal.is_none());
+    }
+
+    #[test]
+    fn a_failed_seal_is_rolled_back() {
+        let root = TempDir::new("seal-rollback");
+        let dir = version(&root);
+        manifest::generate(&dir, "app", "1.0").unwrap();
+        let manifest_before = fs::read(manifest::manifest_path(&dir)).unwrap();
+        testutil::write(&dir.join("docs/readme.txt"), "edited since");
+        // The metadata is written last; a directory in the way of its
+        // temporary file makes that write fail.
+        fs::create_dir_all(fsutil::temp_path(&meta::meta_path(&dir))).unwrap();
+
+        assert!(seal(&dir, "app", "1.0").is_err());
+        assert!(!is_readonly(&dir.join("app.bin")));
+        assert!(!is_readonly(&dir.join("docs/readme.txt")));
+        assert!(!is_readonly(&manifest::manifest_path(&dir)));
+        assert_eq!(
+            fs::read(manifest::manifest_path(&dir)).unwrap(),
+            manifest_before
+        );
+        assert!(meta::read(&dir).unwrap().seal.is_none());
+    }
+
+    #[test]
+    fn rotted_files_of_sealed_versions_are_not_pushed() {
+        let tmp = TempDir::new("seal-push");
+        let dir = version(&tmp);
+        let root = tmp.join("local");
+        testutil::write(&root.join("app/2.0/app.bin"), "unsealed");
+        seal(&dir, "app", "1.0").unwrap();
+
+        let mut check = PushCheck::new(&root);
+        check.ensure_pushable(Path::new("app/1.0/app.bin")).unwrap();
+        check.ensure_pushable(Path::new("app/2.0/app.bin")).unwrap();
+
+        fsutil::set_readonly(&dir.join("app.bin"), false).unwrap();
+        fs::write(dir.join("app.bin"), "bitrot").unwrap();
+        let mut check = PushCheck::new(&root);
+        assert!(matches!(
+            check.ensure_pushable(Path::new("app/1.0/app.bin")),
+            Err(Error::Sealed(_))
+        ));
+        check
+            .ensure_pushable(Path::new("app/1.0/docs/readme.txt"))
+            .unwrap();
+        assert!(check
+            .ensure_pushable(Path::new("app/1.0/unknown.txt"))
+            .is_err());
+    }
+}
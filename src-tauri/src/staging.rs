@@ -0,0 +1,233 @@
+use crate::catalog;
+use crate::error::{Error, Result};
+use crate::names;
+use crate::scope::{plain_name, Scope};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Versions are assembled here, inside the storage root so the final rename
+/// never crosses a filesystem. The leading dot keeps it out of the catalog.
+pub const STAGING_DIR: &str = ".staging";
+
+/// A scratch directory below `<root>/.staging` in which a new version is
+/// built. It is removed when dropped, whether or not the version was
+/// installed, so a failed operation leaves nothing in the catalog.
+pub struct Staging {
+    dir: PathBuf,
+    /// The `.staging` directory of a `beside` staging, removed with it once
+    /// empty.
+    holder: Option<PathBuf>,
+}
+
+impl Staging {
+    /// Creates `<root>/.staging/<operation>-<nanos>-<pid>`. The process id
+    /// lets `clean_abandoned` tell live directories from those of a run
+    /// that crashed.
+    pub fn new(root: &Path, operation: &str) -> Result<Staging> {
+        Ok(Staging {
+            dir: create(&root.join(STAGING_DIR), operation)?,
+            holder: None,
+        })
+    }
+
+    /// Like `new`, but in the directory that will hold `target`, so content
+    /// can be renamed into place even when that directory is on another
+    /// filesystem than the root.
+    pub fn beside(target: &Path, operation: &str) -> Result<Staging> {
+        let holder = target
+            .parent()
+            .ok_or_else(|| Error::InvalidArgument(format!("{} has no parent", target.display())))?
+            .join(STAGING_DIR);
+        Ok(Staging {
+            dir: create(&holder, operation)?,
+            holder: Some(holder),
+        })
+    }
+
+    /// Where the version content is written.
+    pub fn content(&self) -> PathBuf {
+        self.dir.join("content")
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                eprintln!("Error removing staging directory {:?}: {}", self.dir, e);
+            }
+            _ => {}
+        }
+        if let Some(holder) = &self.holder {
+            // Fails, as it should, while other operations still use it.
+            let _ = fs::remove_dir(holder);
+        }
+    }
+}
+
+fn create(holder: &Path, operation: &str) -> Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let dir = holder.join(format!("{}-{:x}-{}", operation, nanos, std::process::id()));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// `<root>/<program>/<version>` for a version about to be created. The
+/// version name must follow the naming rules, and so must the program name
+/// unless the program already exists.
+fn target_dir(root: &Path, program: &str, version: &str) -> Result<PathBuf> {
+    let program_dir = root.join(plain_name(program)?);
+    if fs::symlink_metadata(&program_dir).is_err() {
+        names::validate(program)?;
+    }
+    Ok(program_dir.join(names::validate(version)?))
+}
+
+/// Fails early if `<root>/<program>/<version>` already exists, possibly
+/// under a differently normalised name, before any work is spent on
+/// building it.
+pub fn ensure_absent(root: &Path, program: &str, version: &str) -> Result<()> {
+    let target = target_dir(root, program, version)?;
+    if fs::symlink_metadata(&target).is_ok() {
+        return Err(Error::AlreadyExists(format!("{}/{}", program, version)));
+    }
+    if let Some(existing) = names::find_equivalent(&root.join(program), version)? {
+        return Err(Error::AlreadyExists(format!("{}/{}", program, existing)));
+    }
+    if fs::symlink_metadata(root.join(program)).is_err() {
+        if let Some(existing) = names::find_equivalent(root, program)? {
+            return Err(Error::AlreadyExists(existing));
+        }
+    }
+    Ok(())
+}
+
+/// Renames finished `content` into place as `<root>/<program>/<version>`,
+/// unless that appeared in the meantime.
+pub fn install(
+    root: &Path,
+    scope: &Scope,
+    content: &Path,
+    program: &str,
+    version: &str,
+) -> Result<PathBuf> {
+    let target = target_dir(root, program, version)?;
+    if let Some(program_dir) = target.parent() {
+        fs::create_dir_all(program_dir)?;
+    }
+    let target = scope.check(&target)?;
+    ensure_absent(root, program, version)?;
+    fs::rename(content, &target)?;
+    Ok(target)
+}
+
+/// Removes staging directories left behind by earlier runs that crashed or
+/// were killed half-way, in `<root>/.staging` and in the `.staging` of each
+/// program that `Staging::beside` used. Returns the number removed.
+pub fn clean_abandoned(root: &Path) -> Result<usize> {
+    let mut removed = clean_holder(&root.join(STAGING_DIR))?;
+    let programs = match catalog::list_subdirs(root) {
+        Ok(programs) => programs,
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => return Ok(removed),
+        Err(e) => return Err(e),
+    };
+    for (_, program_dir) in programs {
+        let holder = Path::new(&program_dir).join(STAGING_DIR);
+        removed += clean_holder(&holder)?;
+        // Fails, as it should, while a live operation still uses it.
+        let _ = fs::remove_dir(&holder);
+    }
+    Ok(removed)
+}
+
+fn clean_holder(dir: &Path) -> Result<usize> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let own_suffix = format!("-{}", std::process::id());
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().ends_with(&own_suffix) {
+            continue;
+        }
+        let path = entry.path();
+        let result = if fs::symlink_metadata(&path)?.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => removed += 1,
+            Err(e) => eprintln!("Error removing abandoned staging entry {:?}: {}", path, e),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::testutil::{self, TempDir};
+
+    #[test]
+    fn cleans_abandoned_staging_next_to_programs() {
+        let dir = TempDir::new("staging");
+        let root = dir.join("root");
+        testutil::write(&root.join(".staging/import-1-999999999/content/f"), "x");
+        testutil::write(&root.join("App/.staging/move-1-999999999/content/f"), "x");
+        testutil::write(&root.join("App/1.0/f"), "x");
+        let live = Staging::beside(&root.join("App/2.0"), "move").unwrap();
+
+        assert_eq!(clean_abandoned(&root).unwrap(), 2);
+        assert!(live.content().parent().unwrap().is_dir());
+        assert!(!root.join(".staging/import-1-999999999").exists());
+        assert!(!root.join("App/.staging/move-1-999999999").exists());
+        assert!(root.join("App/1.0/f").exists());
+
+        drop(live);
+        assert!(!root.join("App/.staging").exists());
+        assert_eq!(clean_abandoned(&dir.join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn installs_into_free_names_only() {
+        let dir = TempDir::new("staging-install");
+        let root = dir.join("root");
+        testutil::write(&root.join("App/1.0/f"), "x");
+        // "Café" decomposed, as an older macOS might have written it.
+        testutil::write(&root.join("Cafe\u{301}/1.0/f"), "x");
+        let scope = Scope::from_config(&AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let staging = Staging::new(&root, "import").unwrap();
+        let content = staging.content();
+        testutil::write(&content.join("f"), "new");
+        assert!(matches!(
+            install(&root, &scope, &content, "App", "1.0"),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            ensure_absent(&root, "Caf\u{e9}", "2.0"),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(ensure_absent(&root, "App", "../2.0").is_err());
+
+        let target = install(&root, &scope, &content, "App", "2.0").unwrap();
+        assert_eq!(testutil::read(&target.join("f")).as_deref(), Some("new"));
+        let staged = content.parent().unwrap().to_path_buf();
+        drop(staging);
+        assert!(!staged.exists());
+    }
+}
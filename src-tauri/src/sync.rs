@@ -0,0 +1,401 @@
+use crate::bisync;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::seal;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+pub const SYNC_PROGRESS_EVENT: &str = "mirror-sync-progress";
+pub const SYNC_FINISHED_EVENT: &str = "mirror-sync-finished";
+
+/// The job holding the `RunGuard`, if any.
+static RUNNING: Mutex<Option<&'static str>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncMode {
+    /// Copy new and changed files from local to mirror.
+    #[default]
+    OneWay,
+    /// Propagate changes in both directions and report conflicts.
+    TwoWay,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncOptions {
+    #[serde(default)]
+    pub mode: SyncMode,
+    /// Compare contents by SHA-256 when size and mtime match.
+    #[serde(default)]
+    pub verify_hash: bool,
+    /// Limit the sync to these programs; all programs when empty.
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Two-way only: delete files on one side that were deleted on the other.
+    #[serde(default)]
+    pub propagate_deletes: bool,
+    /// Two-way only: go ahead even if more files would be deleted than
+    /// `bisync::MAX_UNCONFIRMED_DELETES`.
+    #[serde(default)]
+    pub confirm_deletes: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileStatus {
+    Copying,
+    Added,
+    Updated,
+    Unchanged,
+    /// Copied from the mirror to local (two-way only).
+    Pulled,
+    Deleted,
+    Conflict,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub path: String,
+    pub status: FileStatus,
+    pub bytes_copied: u64,
+    pub file_size: u64,
+    pub files_done: usize,
+    pub files_total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    pub files_total: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub pulled: usize,
+    pub deleted: usize,
+    pub conflicts: usize,
+    pub failed: usize,
+    pub bytes_copied: u64,
+    pub failures: Vec<SyncFailure>,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Guard that keeps jobs touching the mirror from running at once: syncs,
+/// restores and renames write to it, and a scrub hashing a file while it
+/// is rewritten would report corruption that is not there.
+pub struct RunGuard;
+
+impl RunGuard {
+    /// `job` names the caller in the error others get, e.g. "a scrub".
+    pub fn acquire(job: &'static str) -> Result<RunGuard> {
+        let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(other) = *running {
+            return Err(Error::Busy(format!("{} is already running", other)));
+        }
+        *running = Some(job);
+        Ok(RunGuard)
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Checks that both roots are configured, that the mirror root exists and
+/// that neither contains the other. The mirror root is never created: when
+/// the drive is not mounted, creating it would put an empty mirror on the
+/// system disk.
+pub fn mirror_roots(config: &config::AppConfig) -> Result<(PathBuf, PathBuf)> {
+    if config.mirror_path.trim().is_empty() {
+        return Err(Error::Config("no mirror path is configured".into()));
+    }
+    let local = fs::canonicalize(config.local_root())?;
+    let mirror = config.mirror_root();
+    if !mirror.is_dir() {
+        return Err(Error::MirrorUnavailable(format!(
+            "{} does not exist; is the drive mounted?",
+            mirror.display()
+        )));
+    }
+    let mirror = fs::canonicalize(mirror)?;
+    if local.starts_with(&mirror) || mirror.starts_with(&local) {
+        return Err(Error::Config(
+            "local and mirror paths must not contain each other".into(),
+        ));
+    }
+    Ok((local, mirror))
+}
+
+/// Whether `relative` lies in one of `programs`; an empty list matches all.
+pub fn in_programs(relative: &Path, programs: &[String]) -> bool {
+    programs.is_empty()
+        || relative
+            .components()
+            .next()
+            .map(|c| programs.iter().any(|p| c.as_os_str() == p.as_str()))
+            .unwrap_or(false)
+}
+
+fn needs_copy(src: &fs::Metadata, src_path: &Path, dest: &Path, verify_hash: bool) -> Result<bool> {
+    let dest_meta = match fs::metadata(dest) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e.into()),
+    };
+    if dest_meta.len() != src.len() || !fsutil::same_mtime(src, &dest_meta) {
+        return Ok(true);
+    }
+    if verify_hash {
+        return Ok(fsutil::sha256_file(src_path)? != fsutil::sha256_file(dest)?);
+    }
+    Ok(false)
+}
+
+/// Copies new and changed files from `local` to `mirror`. Nothing is ever
+/// deleted from the mirror. Per-file failures are recorded in the summary
+/// and do not stop the sync.
+pub fn sync_to_mirror<F>(
+    local: &Path,
+    mirror: &Path,
+    options: &SyncOptions,
+    mut on_progress: F,
+) -> Result<SyncSummary>
+where
+    F: FnMut(&SyncProgress),
+{
+    let started = Instant::now();
+    let files: Vec<_> = fsutil::walk_files(local, true)?
+        .into_iter()
+        .filter(|f| in_programs(&f.relative, &options.programs))
+        .collect();
+
+    let mut summary = SyncSummary {
+        files_total: files.len(),
+        ..Default::default()
+    };
+    let mut pushable = seal::PushCheck::new(local);
+
+    for (i, file) in files.iter().enumerate() {
+        let dest = mirror.join(&file.relative);
+        let rel = file.relative.to_string_lossy().into_owned();
+        let size = file.metadata.len();
+        let mut progress = SyncProgress {
+            path: rel.clone(),
+            status: FileStatus::Unchanged,
+            bytes_copied: 0,
+            file_size: size,
+            files_done: i,
+            files_total: files.len(),
+            error: None,
+        };
+
+        let existed = dest.exists();
+        let result =
+            needs_copy(&file.metadata, &file.path, &dest, options.verify_hash).and_then(|copy| {
+                if !copy {
+                    return Ok(false);
+                }
+                pushable.ensure_pushable(&file.relative)?;
+                progress.status = FileStatus::Copying;
+                fsutil::copy_file_atomic(&file.path, &dest, |copied| {
+                    progress.bytes_copied = copied;
+                    on_progress(&progress);
+                })?;
+                Ok(true)
+            });
+
+        progress.files_done = i + 1;
+        match result {
+            Ok(true) => {
+                summary.bytes_copied += size;
+                if existed {
+                    summary.updated += 1;
+                    progress.status = FileStatus::Updated;
+                } else {
+                    summary.added += 1;
+                    progress.status = FileStatus::Added;
+                }
+            }
+            Ok(false) => {
+                summary.unchanged += 1;
+                progress.status = FileStatus::Unchanged;
+            }
+            Err(e) => {
+                summary.failed += 1;
+                progress.status = FileStatus::Failed;
+                progress.error = Some(e.to_string());
+                summary.failures.push(SyncFailure {
+                    path: rel,
+                    error: e.to_string(),
+                });
+            }
+        }
+        on_progress(&progress);
+    }
+
+    summary.duration_ms = started.elapsed().as_millis();
+    Ok(summary)
+}
+
+#[tauri::command(async)]
+pub fn sync_mirror(app: AppHandle, options: Option<SyncOptions>) -> Result<SyncSummary> {
+    let _guard = RunGuard::acquire("a mirror sync")?;
+    let options = options.unwrap_or_default();
+    let emit = |progress: &SyncProgress| {
+        let _ = app.emit_all(SYNC_PROGRESS_EVENT, progress);
+    };
+
+    let summary = match options.mode {
+        SyncMode::OneWay => {
+            let config = config::load(&app)?;
+            let (local, mirror) = mirror_roots(&config)?;
+            sync_to_mirror(&local, &mirror, &options, emit)?
+        }
+        SyncMode::TwoWay => bisync::run(&app, &options, emit)?,
+    };
+    let _ = app.emit_all(SYNC_FINISHED_EVENT, &summary);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+    use filetime::FileTime;
+
+    fn roots(dir: &TempDir) -> (PathBuf, PathBuf) {
+        let (local, mirror) = (dir.join("local"), dir.join("mirror"));
+        testutil::write(&local.join("app/1.0/app.bin"), "binary");
+        testutil::write(&local.join("app/1.0/docs/readme.txt"), "readme");
+        testutil::write(&local.join("tool/2.0/tool.bin"), "tool");
+        testutil::write(&local.join(".trash/old/app.bin"), "trashed");
+        fs::create_dir_all(&mirror).unwrap();
+        (local, mirror)
+    }
+
+    fn sync(local: &Path, mirror: &Path, options: &SyncOptions) -> SyncSummary {
+        sync_to_mirror(local, mirror, options, |_| {}).unwrap()
+    }
+
+    #[test]
+    fn copies_new_and_changed_files_and_never_deletes() {
+        let dir = TempDir::new("sync");
+        let (local, mirror) = roots(&dir);
+        testutil::write(&mirror.join("app/0.9/app.bin"), "only on the mirror");
+
+        let first = sync(&local, &mirror, &SyncOptions::default());
+        assert_eq!((first.files_total, first.added, first.failed), (3, 3, 0));
+        assert!(!mirror.join(".trash").exists());
+
+        testutil::write(&local.join("app/1.0/app.bin"), "rebuilt binary");
+        fs::remove_file(local.join("tool/2.0/tool.bin")).unwrap();
+        let second = sync(&local, &mirror, &SyncOptions::default());
+        assert_eq!((second.updated, second.unchanged), (1, 1));
+        assert_eq!(
+            testutil::read(&mirror.join("app/1.0/app.bin")).as_deref(),
+            Some("rebuilt binary")
+        );
+        assert!(mirror.join("tool/2.0/tool.bin").exists());
+        assert!(mirror.join("app/0.9/app.bin").exists());
+    }
+
+    #[test]
+    fn hashing_finds_changes_that_keep_size_and_mtime() {
+        let dir = TempDir::new("sync-hash");
+        let (local, mirror) = roots(&dir);
+        sync(&local, &mirror, &SyncOptions::default());
+        let file = local.join("app/1.0/app.bin");
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(&file).unwrap());
+        fs::write(&file, "BINARY").unwrap();
+        filetime::set_file_mtime(&file, mtime).unwrap();
+
+        assert_eq!(sync(&local, &mirror, &SyncOptions::default()).updated, 0);
+        let options = SyncOptions {
+            verify_hash: true,
+            ..Default::default()
+        };
+        assert_eq!(sync(&local, &mirror, &options).updated, 1);
+        assert_eq!(
+            testutil::read(&mirror.join("app/1.0/app.bin")).as_deref(),
+            Some("BINARY")
+        );
+    }
+
+    #[test]
+    fn only_the_chosen_programs_are_synced() {
+        let dir = TempDir::new("sync-programs");
+        let (local, mirror) = roots(&dir);
+        let options = SyncOptions {
+            programs: vec!["tool".into()],
+            ..Default::default()
+        };
+        assert_eq!(sync(&local, &mirror, &options).added, 1);
+        assert!(mirror.join("tool/2.0/tool.bin").exists());
+        assert!(!mirror.join("app").exists());
+    }
+
+    #[test]
+    fn a_rotted_sealed_file_does_not_replace_the_mirror_copy() {
+        let dir = TempDir::new("sync-sealed");
+        let (local, mirror) = roots(&dir);
+        let version = local.join("app/1.0");
+        seal::seal(&version, "app", "1.0").unwrap();
+        sync(&local, &mirror, &SyncOptions::default());
+
+        fsutil::set_readonly(&version.join("app.bin"), false).unwrap();
+        fs::write(version.join("app.bin"), "binary, rotted").unwrap();
+        let summary = sync(&local, &mirror, &SyncOptions::default());
+        assert_eq!(summary.failed, 1);
+        assert!(summary.failures[0].path.ends_with("app.bin"));
+        assert_eq!(
+            testutil::read(&mirror.join("app/1.0/app.bin")).as_deref(),
+            Some("binary")
+        );
+    }
+
+    #[test]
+    fn the_mirror_root_must_exist_and_stay_apart() {
+        let dir = TempDir::new("sync-roots");
+        fs::create_dir_all(dir.join("local")).unwrap();
+        let config = config::AppConfig {
+            local_path: dir.join("local").to_string_lossy().into_owned(),
+            mirror_path: dir.join("unplugged").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            mirror_roots(&config),
+            Err(Error::MirrorUnavailable(_))
+        ));
+        assert!(!dir.join("unplugged").exists());
+
+        fs::create_dir_all(dir.join("local/mirror")).unwrap();
+        let nested = config::AppConfig {
+            mirror_path: dir.join("local/mirror").to_string_lossy().into_owned(),
+            ..config.clone()
+        };
+        assert!(matches!(mirror_roots(&nested), Err(Error::Config(_))));
+        let unset = config::AppConfig {
+            mirror_path: " ".into(),
+            ..config
+        };
+        assert!(matches!(mirror_roots(&unset), Err(Error::Config(_))));
+    }
+}
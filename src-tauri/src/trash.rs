@@ -0,0 +1,344 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::scope::Scope;
+use crate::seal;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+/// Trash lives inside the storage root so that trashing is a cheap rename.
+/// The leading dot keeps it out of the program list.
+pub const TRASH_DIR: &str = ".trash";
+const ENTRY_FILE: &str = "entry.json";
+const DATA_DIR: &str = "data";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: String,
+    pub program: String,
+    pub version: String,
+    pub original_path: String,
+    /// For a single file trashed by a sync, its path inside the version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Seconds since the unix epoch.
+    pub trashed_at: u64,
+    pub size: u64,
+}
+
+fn trash_root(root: &Path) -> PathBuf {
+    root.join(TRASH_DIR)
+}
+
+fn entry_dir(root: &Path, id: &str) -> Result<PathBuf> {
+    if id.is_empty() || id.contains(['/', '\\']) || id == "." || id == ".." {
+        return Err(Error::InvalidArgument(format!("trash id {:?}", id)));
+    }
+    Ok(trash_root(root).join(id))
+}
+
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+fn new_id(root: &Path) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut id = format!("{:x}", nanos);
+    let mut n = 1;
+    while trash_root(root).join(&id).exists() {
+        id = format!("{:x}-{}", nanos, n);
+        n += 1;
+    }
+    id
+}
+
+fn read_entry(dir: &Path) -> Result<TrashEntry> {
+    let text = fs::read_to_string(dir.join(ENTRY_FILE))?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+pub fn move_to_trash(
+    root: &Path,
+    scope: &Scope,
+    program: &str,
+    version: &str,
+) -> Result<TrashEntry> {
+    let source = scope.version_dir(root, program, version)?;
+    seal::ensure_unsealed(scope, &source)?;
+    trash(root, &source, program, version, None)
+}
+
+/// Moves a single file into the trash instead of deleting it. `key` is its
+/// `/`-separated path below `root`, as sync uses it.
+pub fn move_file_to_trash(root: &Path, key: &str) -> Result<TrashEntry> {
+    let mut parts = key.splitn(3, '/');
+    let program = parts.next().unwrap_or_default();
+    let version = parts.next().unwrap_or_default();
+    let file = parts.next().map(str::to_string);
+    let source = key
+        .split('/')
+        .fold(root.to_path_buf(), |p, part| p.join(part));
+    if !source.is_file() {
+        return Err(Error::NotFound(source.display().to_string()));
+    }
+    trash(root, &source, program, version, file)
+}
+
+fn trash(
+    root: &Path,
+    source: &Path,
+    program: &str,
+    version: &str,
+    file: Option<String>,
+) -> Result<TrashEntry> {
+    let size = dir_size(source)?;
+
+    let id = new_id(root);
+    let dir = trash_root(root).join(&id);
+    fs::create_dir_all(&dir)?;
+    if let Err(e) = fs::rename(source, dir.join(DATA_DIR)) {
+        let _ = fs::remove_dir(&dir);
+        return Err(e.into());
+    }
+
+    let entry = TrashEntry {
+        id,
+        program: program.to_string(),
+        version: version.to_string(),
+        original_path: source.to_string_lossy().into_owned(),
+        file,
+        trashed_at: fsutil::now_secs(),
+        size,
+    };
+    let json = serde_json::to_string_pretty(&entry)?;
+    if let Err(e) = fs::write(dir.join(ENTRY_FILE), json) {
+        // Without metadata the entry could never be restored, so undo the move.
+        let _ = fs::rename(dir.join(DATA_DIR), source);
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e.into());
+    }
+    Ok(entry)
+}
+
+pub fn list(root: &Path) -> Result<Vec<TrashEntry>> {
+    let dir = trash_root(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for item in fs::read_dir(&dir)? {
+        let item = item?;
+        if !item.path().is_dir() {
+            continue;
+        }
+        match read_entry(&item.path()) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("Skipping unreadable trash entry {:?}: {}", item.path(), e),
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.trashed_at));
+    Ok(entries)
+}
+
+pub fn restore(root: &Path, scope: &Scope, id: &str) -> Result<TrashEntry> {
+    let dir = entry_dir(root, id)?;
+    let entry = read_entry(&dir)?;
+    // Restore relative to the current root in case the storage path moved.
+    let program_dir = scope.check(&root.join(&entry.program))?;
+    let target = [
+        entry.version.as_str(),
+        entry.file.as_deref().unwrap_or_default(),
+    ]
+    .iter()
+    .filter(|part| !part.is_empty())
+    .flat_map(|part| part.split('/'))
+    .fold(program_dir, |p, part| p.join(part));
+    if target.exists() {
+        return Err(Error::AlreadyExists(target.display().to_string()));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let target = scope.check(&target)?;
+    fs::rename(dir.join(DATA_DIR), &target)?;
+    fs::remove_dir_all(&dir)?;
+    Ok(entry)
+}
+
+pub fn purge(root: &Path, id: &str) -> Result<()> {
+    let dir = entry_dir(root, id)?;
+    if !dir.is_dir() {
+        return Err(Error::NotFound(format!("trash entry {}", id)));
+    }
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+/// Permanently removes entries older than `retention_days`. A retention of
+/// zero disables expiry.
+pub fn purge_expired(root: &Path, retention_days: u64) -> Result<Vec<TrashEntry>> {
+    if retention_days == 0 {
+        return Ok(Vec::new());
+    }
+    let cutoff = fsutil::now_secs().saturating_sub(retention_days * 24 * 60 * 60);
+    let mut purged = Vec::new();
+    for entry in list(root)? {
+        if entry.trashed_at < cutoff {
+            purge(root, &entry.id)?;
+            purged.push(entry);
+        }
+    }
+    Ok(purged)
+}
+
+#[tauri::command]
+pub fn trash_version(app: AppHandle, program: String, version: String) -> Result<TrashEntry> {
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    move_to_trash(&config.local_root(), &scope, &program, &version)
+}
+
+#[tauri::command]
+pub fn list_trash(app: AppHandle) -> Result<Vec<TrashEntry>> {
+    let config = config::load(&app)?;
+    list(&config.local_root())
+}
+
+#[tauri::command]
+pub fn restore_trash(app: AppHandle, id: String) -> Result<TrashEntry> {
+    let config = config::load(&app)?;
+    let scope = Scope::from_config(&config)?;
+    restore(&config.local_root(), &scope, &id)
+}
+
+#[tauri::command]
+pub fn purge_trash(app: AppHandle, id: String) -> Result<()> {
+    let config = config::load(&app)?;
+    purge(&config.local_root(), &id)
+}
+
+#[tauri::command]
+pub fn purge_expired_trash(app: AppHandle) -> Result<Vec<TrashEntry>> {
+    let config = config::load(&app)?;
+    purge_expired(&config.local_root(), config.trash_retention_days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::testutil::{self, TempDir};
+
+    fn local_root(dir: &TempDir) -> (PathBuf, Scope) {
+        let root = dir.join("local");
+        testutil::write(&root.join("app/1.0/app.bin"), "binary");
+        testutil::write(&root.join("app/1.0/docs/readme.txt"), "readme");
+        let scope = Scope::from_config(&AppConfig {
+            local_path: root.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        (root, scope)
+    }
+
+    #[test]
+    fn a_trashed_version_can_be_restored() {
+        let dir = TempDir::new("trash");
+        let (root, scope) = local_root(&dir);
+        let entry = move_to_trash(&root, &scope, "app", "1.0").unwrap();
+        assert_eq!(entry.size, 12);
+        assert!(!root.join("app/1.0").exists());
+        assert_eq!(list(&root).unwrap().len(), 1);
+
+        restore(&root, &scope, &entry.id).unwrap();
+        assert_eq!(
+            testutil::read(&root.join("app/1.0/docs/readme.txt")).as_deref(),
+            Some("readme")
+        );
+        assert!(list(&root).unwrap().is_empty());
+        assert!(restore(&root, &scope, &entry.id).is_err());
+    }
+
+    #[test]
+    fn restoring_never_overwrites() {
+        let dir = TempDir::new("trash-overwrite");
+        let (root, scope) = local_root(&dir);
+        let entry = move_to_trash(&root, &scope, "app", "1.0").unwrap();
+        testutil::write(&root.join("app/1.0/app.bin"), "rebuilt");
+        assert!(matches!(
+            restore(&root, &scope, &entry.id),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert_eq!(list(&root).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_single_file_goes_back_into_its_version() {
+        let dir = TempDir::new("trash-file");
+        let (root, scope) = local_root(&dir);
+        let entry = move_file_to_trash(&root, "app/1.0/docs/readme.txt").unwrap();
+        assert_eq!(entry.file.as_deref(), Some("docs/readme.txt"));
+        assert!(!root.join("app/1.0/docs/readme.txt").exists());
+        assert!(move_file_to_trash(&root, "app/1.0/docs/readme.txt").is_err());
+
+        restore(&root, &scope, &entry.id).unwrap();
+        assert!(root.join("app/1.0/docs/readme.txt").is_file());
+    }
+
+    #[test]
+    fn sealed_versions_and_odd_names_are_refused() {
+        let dir = TempDir::new("trash-refused");
+        let (root, scope) = local_root(&dir);
+        assert!(move_to_trash(&root, &scope, "app", "..").is_err());
+        assert!(move_to_trash(&root, &scope, "app", "2.0").is_err());
+        assert!(purge(&root, "../app").is_err());
+
+        seal::seal(&root.join("app/1.0"), "app", "1.0").unwrap();
+        assert!(matches!(
+            move_to_trash(&root, &scope, "app", "1.0"),
+            Err(Error::Sealed(_))
+        ));
+        assert!(root.join("app/1.0/app.bin").exists());
+    }
+
+    #[test]
+    fn only_expired_entries_are_purged() {
+        let dir = TempDir::new("trash-expiry");
+        let (root, scope) = local_root(&dir);
+        testutil::write(&root.join("app/2.0/app.bin"), "binary");
+        let old = move_to_trash(&root, &scope, "app", "1.0").unwrap();
+        let recent = move_to_trash(&root, &scope, "app", "2.0").unwrap();
+        let aged = TrashEntry {
+            trashed_at: 0,
+            ..old.clone()
+        };
+        fs::write(
+            trash_root(&root).join(&old.id).join(ENTRY_FILE),
+            serde_json::to_string(&aged).unwrap(),
+        )
+        .unwrap();
+
+        assert!(purge_expired(&root, 0).unwrap().is_empty());
+        let purged = purge_expired(&root, 30).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].id, old.id);
+        let left: Vec<_> = list(&root).unwrap().into_iter().map(|e| e.id).collect();
+        assert_eq!(left, [recent.id]);
+    }
+}
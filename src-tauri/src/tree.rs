@@ -0,0 +1,135 @@
+use crate::catalog::{compare_entries, VERSION_META_DIR};
+use crate::config;
+use crate::error::{Error, Result};
+use crate::scope::Scope;
+use serde::Serialize;
+use std::fs::{self, DirEntry};
+use std::path::Path;
+use tauri::AppHandle;
+
+pub const DEFAULT_PAGE_SIZE: usize = 500;
+pub const MAX_PAGE_SIZE: usize = 5000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeEntry {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+    /// File size in bytes; zero for directories.
+    pub size: u64,
+    /// Number of direct children; `None` for files or unreadable directories.
+    pub child_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreePage {
+    pub path: String,
+    pub entries: Vec<TreeEntry>,
+    /// Total number of entries in the directory, across all pages.
+    pub total: usize,
+    /// Pass back as `cursor` to fetch the next page; `None` on the last page.
+    pub next_cursor: Option<usize>,
+}
+
+/// Whether a directory entry shows in the tree; version metadata does not.
+fn listed(entry: &DirEntry) -> bool {
+    entry.file_name() != VERSION_META_DIR
+}
+
+/// Returns one page of a single directory level, sorted the same way as the
+/// module view. Only the entries on the requested page are stat'ed.
+pub fn list_page(dir: &Path, cursor: usize, limit: usize) -> Result<TreePage> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !listed(&entry) {
+            continue;
+        }
+        let is_directory = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        names.push((
+            is_directory,
+            entry.file_name().to_string_lossy().into_owned(),
+        ));
+    }
+    names.sort_by(|a, b| compare_entries(a.0, &a.1, b.0, &b.1));
+
+    let total = names.len();
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let end = cursor.saturating_add(limit).min(total);
+    let entries = names
+        .get(cursor..end)
+        .unwrap_or_default()
+        .iter()
+        .map(|(is_directory, name)| {
+            let path = dir.join(name);
+            let (size, child_count) = if *is_directory {
+                let children = fs::read_dir(&path)
+                    .ok()
+                    .map(|it| it.filter(|e| e.as_ref().map_or(true, listed)).count());
+                (0, children)
+            } else {
+                (fs::metadata(&path).map(|m| m.len()).unwrap_or(0), None)
+            };
+            TreeEntry {
+                name: name.clone(),
+                path: path.to_string_lossy().into_owned(),
+                is_directory: *is_directory,
+                size,
+                child_count,
+            }
+        })
+        .collect();
+
+    Ok(TreePage {
+        path: dir.to_string_lossy().into_owned(),
+        entries,
+        total,
+        next_cursor: (end < total).then_some(end),
+    })
+}
+
+#[tauri::command(async)]
+pub fn list_directory(
+    app: AppHandle,
+    path: String,
+    cursor: Option<usize>,
+    limit: Option<usize>,
+) -> Result<TreePage> {
+    let config = config::load(&app)?;
+    let dir = Scope::from_config(&config)?.check(Path::new(&path))?;
+    if !dir.is_dir() {
+        return Err(Error::NotFound(path));
+    }
+    list_page(
+        &dir,
+        cursor.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_PAGE_SIZE),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TempDir};
+
+    #[test]
+    fn version_metadata_is_neither_listed_nor_counted() {
+        let dir = TempDir::new("tree");
+        testutil::write(&dir.join("app/1.0/app.bin"), "binary");
+        testutil::write(&dir.join("app/1.0/.pm/manifest.json"), "{}");
+        testutil::write(&dir.join("app/readme.txt"), "readme");
+
+        let page = list_page(&dir.join("app"), 0, 10).unwrap();
+        let counts: Vec<_> = page
+            .entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.child_count))
+            .collect();
+        assert_eq!(counts, [("1.0", Some(1)), ("readme.txt", None)]);
+
+        let version = list_page(&dir.join("app/1.0"), 0, 10).unwrap();
+        assert_eq!(version.total, 1);
+    }
+}
@@ -0,0 +1,263 @@
+use semver::{BuildMetadata, Prerelease, Version};
+use serde::Serialize;
+use std::cmp::Ordering;
+
+/// The numbering scheme a version directory name was recognised as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionScheme {
+    /// `1.4.2`, `v2.0.0-rc.1`, or the shorthand `1.4`.
+    SemVer,
+    /// `2024.03`, `2024.3.15`, starting with a four-digit year and a month.
+    CalVer,
+    /// A plain build number such as `1234`, `build-1234` or `r1234`.
+    Build,
+    /// A four-part Windows file version such as `10.0.19041.1`.
+    FileVersion,
+}
+
+/// A parsed version name. Versions of all schemes compare by their numeric
+/// components, so a program that moved from SemVer to CalVer still sorts
+/// in release order.
+#[derive(Debug, Clone)]
+pub struct ParsedVersion {
+    pub scheme: VersionScheme,
+    release: Vec<u64>,
+    pre: Prerelease,
+    build: BuildMetadata,
+}
+
+impl ParsedVersion {
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// The version as SemVer, for matching against ranges. CalVer and
+    /// build numbers are padded with zeros. A fourth component becomes
+    /// build metadata, which ranges ignore: `1.2.3.4` lies in `>=1.2.3`
+    /// and in `=1.2.3`. Names with more components have no SemVer
+    /// equivalent.
+    pub fn to_semver(&self) -> Option<Version> {
+        let build = match self.release.get(3) {
+            _ if self.release.len() > 4 => return None,
+            Some(fourth) if self.build.is_empty() => {
+                BuildMetadata::new(&fourth.to_string()).ok()?
+            }
+            Some(fourth) => BuildMetadata::new(&format!("{}.{}", fourth, self.build)).ok()?,
+            None => self.build.clone(),
+        };
+        let part = |i: usize| self.release.get(i).copied().unwrap_or(0);
+        Some(Version {
+            major: part(0),
+            minor: part(1),
+            patch: part(2),
+            pre: self.pre.clone(),
+            build,
+        })
+    }
+}
+
+impl Ord for ParsedVersion {
+    /// Release components first, missing ones counting as zero, then
+    /// pre-releases before the release, then build metadata.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.release.len().max(other.release.len());
+        (0..len)
+            .map(|i| {
+                let a = self.release.get(i).copied().unwrap_or(0);
+                let b = other.release.get(i).copied().unwrap_or(0);
+                a.cmp(&b)
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.pre.cmp(&other.pre))
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for ParsedVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ParsedVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for ParsedVersion {}
+
+fn is_number(part: &str) -> bool {
+    !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// `build-1234`, `build1234`, `b1234` or `r1234`.
+fn build_number(name: &str) -> Option<u64> {
+    let lower = name.to_ascii_lowercase();
+    let digits = ["build", "b", "r"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .map(|rest| rest.trim_start_matches(['-', '_', '.']))
+        .unwrap_or(&lower);
+    if is_number(digits) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Recognises a version directory name. Returns `None` for names that fit
+/// none of the schemes; those are listed after all others and flagged in
+/// the UI.
+pub fn parse(name: &str) -> Option<ParsedVersion> {
+    if let Some(number) = build_number(name) {
+        return Some(ParsedVersion {
+            scheme: VersionScheme::Build,
+            release: vec![number],
+            pre: Prerelease::EMPTY,
+            build: BuildMetadata::EMPTY,
+        });
+    }
+
+    let text = name.strip_prefix(['v', 'V']).unwrap_or(name);
+    let (text, build) = match text.split_once('+') {
+        Some((text, build)) => (text, BuildMetadata::new(build).ok()?),
+        None => (text, BuildMetadata::EMPTY),
+    };
+    // `2024-03-15` is a date rather than `2024` with a pre-release.
+    let dashed: Vec<&str> = text.split('-').collect();
+    let (parts, pre) = if is_calendar(&dashed) {
+        (dashed, Prerelease::EMPTY)
+    } else {
+        match text.split_once('-') {
+            Some((core, pre)) => (core.split('.').collect(), Prerelease::new(pre).ok()?),
+            None => (text.split('.').collect::<Vec<_>>(), Prerelease::EMPTY),
+        }
+    };
+    if !parts.iter().all(|p| is_number(p)) {
+        return None;
+    }
+    let release = parts
+        .iter()
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    let scheme = if is_calendar(&parts) {
+        VersionScheme::CalVer
+    } else if parts.iter().any(|p| p.len() > 1 && p.starts_with('0')) {
+        return None;
+    } else {
+        match parts.len() {
+            // A single number is a build number unless it has a
+            // pre-release, which is how `2-beta` reads as the shorthand of
+            // `2.0.0-beta`.
+            1 if pre.is_empty() => VersionScheme::Build,
+            1..=3 => VersionScheme::SemVer,
+            4 => VersionScheme::FileVersion,
+            _ => return None,
+        }
+    };
+    Some(ParsedVersion {
+        scheme,
+        release,
+        pre,
+        build,
+    })
+}
+
+/// Whether `parts` start with a four-digit year and a month.
+fn is_calendar(parts: &[&str]) -> bool {
+    let number = |i: usize| parts.get(i).filter(|p| is_number(p))?.parse::<u64>().ok();
+    (2..=4).contains(&parts.len())
+        && parts[0].len() == 4
+        && parts.iter().all(|p| is_number(p))
+        && number(0).is_some_and(|year| (1900..3000).contains(&year))
+        && number(1).is_some_and(|month| (1..=12).contains(&month))
+}
+
+/// Sorts `items` by version name, oldest first. Names that do not parse
+/// come last, by name.
+pub fn sort_by_version<T, F>(items: &mut Vec<T>, name_of: F)
+where
+    F: Fn(&T) -> &str,
+{
+    let mut keyed: Vec<(Option<ParsedVersion>, T)> = items
+        .drain(..)
+        .map(|item| (parse(name_of(&item)), item))
+        .collect();
+    keyed.sort_by(|(a, x), (b, y)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b).then_with(|| name_of(x).cmp(name_of(y))),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => name_of(x).cmp(name_of(y)),
+    });
+    items.extend(keyed.into_iter().map(|(_, item)| item));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheme(name: &str) -> Option<VersionScheme> {
+        parse(name).map(|v| v.scheme)
+    }
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut items: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        sort_by_version(&mut items, |n| n);
+        items
+    }
+
+    #[test]
+    fn recognises_schemes() {
+        assert_eq!(scheme("1.4.2"), Some(VersionScheme::SemVer));
+        assert_eq!(scheme("v2.0.0-rc.1"), Some(VersionScheme::SemVer));
+        assert_eq!(scheme("1.4"), Some(VersionScheme::SemVer));
+        assert_eq!(scheme("2-beta"), Some(VersionScheme::SemVer));
+        assert_eq!(scheme("2024.03"), Some(VersionScheme::CalVer));
+        assert_eq!(scheme("2024-03-15"), Some(VersionScheme::CalVer));
+        assert_eq!(scheme("1234"), Some(VersionScheme::Build));
+        assert_eq!(scheme("build-1234"), Some(VersionScheme::Build));
+        assert_eq!(scheme("r99"), Some(VersionScheme::Build));
+        assert_eq!(scheme("10.0.19041.1"), Some(VersionScheme::FileVersion));
+    }
+
+    #[test]
+    fn rejects_other_names() {
+        assert!(parse("latest").is_none());
+        assert!(parse("1.02.3").is_none());
+        assert!(parse("1.2.3.4.5").is_none());
+        assert!(parse("1..2").is_none());
+        assert!(parse("").is_none());
+    }
+
+    #[test]
+    fn orders_by_components() {
+        assert_eq!(
+            sorted(&["1.10", "1.9.1", "v1.2", "2.0.0-rc.1", "2.0.0", "1.2.0"]),
+            ["1.2.0", "v1.2", "1.9.1", "1.10", "2.0.0-rc.1", "2.0.0"]
+        );
+        assert_eq!(
+            sorted(&["1.2.3.10", "1.2.3", "1.2.3.9", "1.2.4"]),
+            ["1.2.3", "1.2.3.9", "1.2.3.10", "1.2.4"]
+        );
+        assert_eq!(sorted(&["zeta", "1.0", "alpha"]), ["1.0", "alpha", "zeta"]);
+        assert_eq!(parse("1.4"), parse("1.4.0"));
+        assert!(parse("1.0.0-alpha") < parse("1.0.0-beta"));
+    }
+
+    #[test]
+    fn converts_to_semver() {
+        let semver = |name: &str| parse(name).and_then(|v| v.to_semver());
+        assert_eq!(semver("1.4"), Some(Version::new(1, 4, 0)));
+        assert_eq!(semver("2024.3.15"), Some(Version::new(2024, 3, 15)));
+        assert_eq!(semver("build-7"), Some(Version::new(7, 0, 0)));
+
+        let file = semver("10.0.19041.1").unwrap();
+        assert_eq!((file.major, file.minor, file.patch), (10, 0, 19041));
+        assert_eq!(file.build.as_str(), "1");
+        assert_eq!(semver("1.2.3.4+x64").unwrap().build.as_str(), "4.x64");
+    }
+}
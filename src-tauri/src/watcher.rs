@@ -0,0 +1,123 @@
+use crate::catalog;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::index::IndexState;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+pub const PROGRAM_ADDED_EVENT: &str = "catalog-program-added";
+pub const PROGRAM_REMOVED_EVENT: &str = "catalog-program-removed";
+pub const VERSION_ADDED_EVENT: &str = "catalog-version-added";
+pub const VERSION_REMOVED_EVENT: &str = "catalog-version-removed";
+pub const FILE_CHANGED_EVENT: &str = "catalog-file-changed";
+
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Holds the running watcher; dropping it stops watching.
+#[derive(Default)]
+pub struct WatcherState(Mutex<Option<Debouncer<RecommendedWatcher>>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogChange {
+    pub program: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub path: String,
+}
+
+/// Splits a path below the root into program, version and the remainder.
+fn classify(root: &Path, path: &Path) -> Option<(String, Option<String>, usize)> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let program = parts.first()?;
+    if catalog::is_hidden(program) {
+        return None;
+    }
+    Some((program.clone(), parts.get(1).cloned(), parts.len()))
+}
+
+fn handle_events(app: &AppHandle, root: &Path, paths: BTreeSet<PathBuf>) {
+    let state = app.state::<IndexState>();
+    for path in paths {
+        let Some((program, version, depth)) = classify(root, &path) else {
+            continue;
+        };
+
+        match state
+            .lock()
+            .and_then(|mut index| index.sync_path(root, &path))
+        {
+            Ok(_) => {}
+            Err(e) => eprintln!("Error syncing index for {:?}: {}", path, e),
+        }
+
+        let exists = path.exists();
+        let event = match (depth, exists) {
+            (1, true) => PROGRAM_ADDED_EVENT,
+            (1, false) => PROGRAM_REMOVED_EVENT,
+            (2, true) => VERSION_ADDED_EVENT,
+            (2, false) => VERSION_REMOVED_EVENT,
+            _ => FILE_CHANGED_EVENT,
+        };
+        // Stray files directly in the root or a program folder are neither
+        // programs nor versions.
+        if depth <= 2 && exists && !path.is_dir() {
+            continue;
+        }
+        let payload = CatalogChange {
+            program,
+            version,
+            path: path.to_string_lossy().into_owned(),
+        };
+        let _ = app.emit_all(event, payload);
+    }
+}
+
+/// Starts watching the configured storage root, replacing any previous
+/// watcher.
+pub fn start(app: &AppHandle) -> Result<()> {
+    let root = config::load(app)?.local_root();
+    std::fs::create_dir_all(&root)?;
+
+    let handler_app = app.clone();
+    let handler_root = root.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+        Ok(events) => {
+            let paths = events.into_iter().map(|e| e.path).collect();
+            handle_events(&handler_app, &handler_root, paths);
+        }
+        Err(e) => eprintln!("Watcher error: {}", e),
+    })
+    .map_err(|e| Error::Watcher(e.to_string()))?;
+    debouncer
+        .watcher()
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| Error::Watcher(e.to_string()))?;
+
+    let state = app.state::<WatcherState>();
+    let mut current = state
+        .0
+        .lock()
+        .map_err(|_| Error::Watcher("watcher lock poisoned".into()))?;
+    *current = Some(debouncer);
+    Ok(())
+}
+
+/// Called by the frontend after the storage root changes in settings.
+#[tauri::command]
+pub fn restart_watcher(app: AppHandle) -> Result<()> {
+    start(&app)
+}
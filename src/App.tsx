@@ -1,19 +1,43 @@
 import React, { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/tauri";
-import { readDir, readTextFile, writeTextFile, createDir } from "@tauri-apps/api/fs";
+import { listen } from "@tauri-apps/api/event";
+import { readTextFile, writeTextFile } from "@tauri-apps/api/fs";
 import { appDataDir, join } from "@tauri-apps/api/path";
 import { open } from "@tauri-apps/api/shell";
 
 // Type definitions
+type Health = "intact" | "corrupted" | "noManifest";
+
+interface VersionIntegrity {
+  local?: Health;
+  mirror?: Health;
+  checkedAt: number;
+}
+
+interface ParentVersion {
+  program: string;
+  version: string;
+  clonedAt: number;
+}
+
 interface ProgramVersion {
   version: string;
   path: string;
-  modules: FileNode[];
+  modules?: FileNode[];
+  integrity?: VersionIntegrity;
+  sealed: boolean;
+  parent?: ParentVersion;
+  // Missing if the name is not a version number
+  scheme?: "semver" | "calver" | "build" | "fileversion";
+  prerelease: boolean;
 }
 
 interface Program {
   name: string;
+  // Sorted by version number by the backend, oldest first
   versions: ProgramVersion[];
+  latest?: string;
+  latestStable?: string;
 }
 
 interface FileNode {
@@ -23,9 +47,130 @@ interface FileNode {
   children?: FileNode[];
 }
 
+interface TreeEntry {
+  name: string;
+  path: string;
+  isDirectory: boolean;
+  size: number;
+  childCount: number | null;
+}
+
+interface TreePage {
+  path: string;
+  entries: TreeEntry[];
+  total: number;
+  nextCursor: number | null;
+}
+
+interface DropCandidate {
+  path: string;
+  kind: "folder" | "archive";
+  suggestedVersion: string;
+}
+
+interface DropPlan {
+  program: string | null;
+  candidates: DropCandidate[];
+  rejected: string[];
+}
+
+interface IngestProgress {
+  program: string;
+  version: string;
+  source: string;
+  entry: string;
+  done: number;
+  total: number;
+}
+
 interface AppConfig {
   localPath: string;
   mirrorPath: string;
+  // Zero keeps trashed versions until they are purged by hand
+  trashRetentionDays: number;
+  // Zero disables background integrity scrubs
+  scrubIntervalHours: number;
+}
+
+// Error shape returned by Rust commands
+interface CommandError {
+  kind: string;
+  message: string;
+}
+
+interface FileChange {
+  path: string;
+  change: "added" | "removed" | "modified";
+  oldSize?: number;
+  newSize?: number;
+  sizeDelta: number;
+}
+
+interface VersionDiff {
+  program: string;
+  from: string;
+  to: string;
+  changes: FileChange[];
+  added: number;
+  removed: number;
+  modified: number;
+  unchanged: number;
+  sizeDelta: number;
+}
+
+type DiffLayout = "unified" | "sideBySide";
+
+interface DiffLine {
+  kind: "context" | "added" | "removed";
+  oldLine?: number;
+  newLine?: number;
+  text: string;
+}
+
+interface SideBySideRow {
+  kind: "context" | "added" | "removed" | "changed";
+  left?: { number: number; text: string };
+  right?: { number: number; text: string };
+}
+
+interface Hunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  header?: string;
+  lines?: DiffLine[];
+  rows?: SideBySideRow[];
+}
+
+interface KeyChange {
+  path: string;
+  change: "added" | "removed" | "modified";
+  oldValue?: string;
+  newValue?: string;
+}
+
+interface ConfigDiff {
+  format: "json" | "toml" | "yaml" | "ini";
+  changes: KeyChange[];
+  added: number;
+  removed: number;
+  modified: number;
+}
+
+interface FileDiff {
+  oldPath?: string;
+  newPath?: string;
+  kind: "text" | "binary" | "tooLarge";
+  language?: string;
+  identical: boolean;
+  oldSize: number;
+  newSize: number;
+  added: number;
+  removed: number;
+  approximate: boolean;
+  hunks: Hunk[];
+  config?: ConfigDiff;
 }
 
 interface AppState {
@@ -33,8 +178,13 @@ interface AppState {
   selectedProgram: string | null;
   selectedVersion: string | null;
   expandedNodes: Set<string>;
+  directories: Record<string, TreePage>;
   config: AppConfig;
   showSettings: boolean;
+  ingest: IngestProgress | null;
+  diff: VersionDiff | null;
+  fileDiff: FileDiff | null;
+  diffLayout: DiffLayout;
 }
 
 // Styles
@@ -70,6 +220,10 @@ const styles = {
     justifyContent: "space-between",
     alignItems: "center",
   } as React.CSSProperties,
+  headerActions: {
+    display: "flex",
+    gap: "8px",
+  } as React.CSSProperties,
   list: {
     flex: 1,
     overflow: "auto",
@@ -99,6 +253,52 @@ const styles = {
   buttonSecondary: {
     backgroundColor: "#3e3e3e",
   } as React.CSSProperties,
+  warning: {
+    color: "#e5c07b",
+  } as React.CSSProperties,
+  badge: {
+    marginLeft: "6px",
+    padding: "0 4px",
+    fontSize: "10px",
+    borderRadius: "3px",
+    backgroundColor: "#3e3e3e",
+    color: "#9cdcfe",
+  } as React.CSSProperties,
+  diffAdded: {
+    color: "#98c379",
+  } as React.CSSProperties,
+  diffRemoved: {
+    color: "#e06c75",
+  } as React.CSSProperties,
+  diffCode: {
+    fontFamily: "monospace",
+    fontSize: "12px",
+    whiteSpace: "pre",
+    padding: "0 8px",
+    minHeight: "16px",
+  } as React.CSSProperties,
+  diffHunkHeader: {
+    fontFamily: "monospace",
+    fontSize: "12px",
+    padding: "4px 8px",
+    color: "#61afef",
+    backgroundColor: "#2a2a2a",
+  } as React.CSSProperties,
+  diffSide: {
+    width: "50%",
+    overflow: "hidden",
+  } as React.CSSProperties,
+  diffSummary: {
+    padding: "8px 16px",
+    fontSize: "12px",
+    borderBottom: "1px solid #3e3e3e",
+  } as React.CSSProperties,
+  progress: {
+    padding: "8px 16px",
+    fontSize: "12px",
+    color: "#9cdcfe",
+    borderBottom: "1px solid #3e3e3e",
+  } as React.CSSProperties,
   treeNode: {
     paddingLeft: "20px",
   } as React.CSSProperties,
@@ -161,39 +361,22 @@ const styles = {
   } as React.CSSProperties,
 };
 
-// Helper function to read directory recursively
-async function readDirRecursive(path: string): Promise<FileNode[]> {
-  try {
-    const entries = await readDir(path, { recursive: false });
-    const nodes: FileNode[] = [];
-
-    for (const entry of entries) {
-      const node: FileNode = {
-        name: entry.name || "",
-        path: entry.path,
-        isDirectory: !!entry.children,
-      };
-
-      if (node.isDirectory) {
-        try {
-          node.children = await readDirRecursive(entry.path);
-        } catch (e) {
-          node.children = [];
-        }
-      }
-
-      nodes.push(node);
-    }
-
-    return nodes.sort((a, b) => {
-      if (a.isDirectory && !b.isDirectory) return -1;
-      if (!a.isDirectory && b.isDirectory) return 1;
-      return a.name.localeCompare(b.name);
-    });
-  } catch (e) {
-    console.error("Error reading directory:", e);
-    return [];
+// Helper function to turn a rejected invoke() into a user-facing message
+function formatError(e: unknown): string {
+  if (e && typeof e === "object" && "message" in e) {
+    const err = e as CommandError;
+    return err.kind === "outsideScope" || err.kind === "scopeRoot"
+      ? `Blocked: ${err.message}`
+      : err.message;
   }
+  return String(e);
+}
+
+// Helper function to suggest the next version name, e.g. 1.4.3 after 1.4.2
+function nextVersionName(version: string): string {
+  const match = version.match(/^(.*?)(\d+)(\D*)$/);
+  if (!match) return `${version}-copy`;
+  return `${match[1]}${Number(match[2]) + 1}${match[3]}`;
 }
 
 // ProgramsColumn component
@@ -202,11 +385,13 @@ function ProgramsColumn({
   selectedProgram,
   onSelectProgram,
   onAddProgram,
+  onRenameProgram,
 }: {
   programs: Program[];
   selectedProgram: string | null;
   onSelectProgram: (name: string) => void;
   onAddProgram: () => void;
+  onRenameProgram: () => void;
 }) {
   const [hoveredItem, setHoveredItem] = useState<string | null>(null);
 
@@ -214,9 +399,19 @@ function ProgramsColumn({
     <div style={{ ...styles.column, ...styles.programsColumn }}>
       <div style={styles.header}>
         <span>Programs</span>
-        <button style={styles.button} onClick={onAddProgram}>
-          +
-        </button>
+        <div style={styles.headerActions}>
+          {selectedProgram && (
+            <button
+              style={{ ...styles.button, ...styles.buttonSecondary }}
+              onClick={onRenameProgram}
+            >
+              Rename
+            </button>
+          )}
+          <button style={styles.button} onClick={onAddProgram}>
+            +
+          </button>
+        </div>
       </div>
       <div style={styles.list}>
         {programs.map((program) => (
@@ -239,19 +434,31 @@ function ProgramsColumn({
   );
 }
 
+// Helper function to check whether the last scrub found a damaged copy
+function isCorrupted(version: ProgramVersion | undefined): boolean {
+  const integrity = version?.integrity;
+  return integrity?.local === "corrupted" || integrity?.mirror === "corrupted";
+}
+
 // VersionsColumn component
 function VersionsColumn({
   versions,
+  latest,
+  latestStable,
   selectedVersion,
   onSelectVersion,
   onAddVersion,
   programName,
+  ingest,
 }: {
   versions: ProgramVersion[];
+  latest?: string;
+  latestStable?: string;
   selectedVersion: string | null;
   onSelectVersion: (version: string) => void;
   onAddVersion: () => void;
   programName: string | null;
+  ingest: IngestProgress | null;
 }) {
   const [hoveredItem, setHoveredItem] = useState<string | null>(null);
 
@@ -265,6 +472,12 @@ function VersionsColumn({
           </button>
         )}
       </div>
+      {ingest && (
+        <div style={styles.progress} title={ingest.entry || ingest.source}>
+          Adding {ingest.version}...{" "}
+          {ingest.total > 0 ? Math.floor((ingest.done / ingest.total) * 100) : 0}%
+        </div>
+      )}
       <div style={styles.list}>
         {versions.map((version) => (
           <div
@@ -275,10 +488,29 @@ function VersionsColumn({
               ...(selectedVersion === version.version ? styles.listItemSelected : {}),
             }}
             onClick={() => onSelectVersion(version.version)}
+            title={version.parent ? `Cloned from ${version.parent.version}` : undefined}
             onMouseEnter={() => setHoveredItem(version.version)}
             onMouseLeave={() => setHoveredItem(null)}
           >
             {version.version}
+            {version.version === latestStable && <span style={styles.badge}>stable</span>}
+            {version.version === latest && version.version !== latestStable && (
+              <span style={styles.badge}>latest</span>
+            )}
+            {version.sealed && <span title="Sealed"> 🔒</span>}
+            {!version.scheme && (
+              <span style={styles.warning} title="Not a SemVer, CalVer, build number or file version">
+                {" "}?
+              </span>
+            )}
+            {isCorrupted(version) && (
+              <span
+                style={styles.warning}
+                title={`Integrity check failed (local: ${version.integrity?.local ?? "n/a"}, mirror: ${version.integrity?.mirror ?? "n/a"})`}
+              >
+                {" "}⚠
+              </span>
+            )}
           </div>
         ))}
       </div>
@@ -286,19 +518,290 @@ function VersionsColumn({
   );
 }
 
+// Helper function to format a byte count for display
+function formatSize(bytes: number): string {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${unit === 0 ? value : value.toFixed(1)} ${units[unit]}`;
+}
+
+// Helper function to format a size change, e.g. +1.2 KB
+function formatDelta(bytes: number): string {
+  if (bytes === 0) return "±0 B";
+  return `${bytes > 0 ? "+" : "-"}${formatSize(Math.abs(bytes))}`;
+}
+
+// VersionDiffView component renders the changed files of two versions as a tree
+function VersionDiffView({
+  diff,
+  onClose,
+  onSelectChange,
+}: {
+  diff: VersionDiff;
+  onClose: () => void;
+  onSelectChange: (change: FileChange) => void;
+}) {
+  const markers = {
+    added: { sign: "+", style: styles.diffAdded },
+    removed: { sign: "-", style: styles.diffRemoved },
+    modified: { sign: "~", style: styles.warning },
+  };
+  const rows: React.ReactNode[] = [];
+  let openDirs: string[] = [];
+
+  for (const change of diff.changes) {
+    const parts = change.path.split("/");
+    const dirs = parts.slice(0, -1);
+    // Emit a row for each directory level not shared with the previous file
+    let shared = 0;
+    while (shared < dirs.length && dirs[shared] === openDirs[shared]) shared++;
+    for (let level = shared; level < dirs.length; level++) {
+      rows.push(
+        <div
+          key={`dir:${dirs.slice(0, level + 1).join("/")}`}
+          style={{ ...styles.treeItem, paddingLeft: `${level * 20 + 8}px` }}
+        >
+          <span style={styles.icon}>📁</span>
+          {dirs[level]}
+        </div>
+      );
+    }
+    openDirs = dirs;
+
+    const marker = markers[change.change];
+    rows.push(
+      <div
+        key={change.path}
+        style={{ ...styles.treeItem, ...marker.style, paddingLeft: `${dirs.length * 20 + 8}px` }}
+        onClick={() => onSelectChange(change)}
+        title={`${change.oldSize !== undefined ? formatSize(change.oldSize) : "-"} → ${
+          change.newSize !== undefined ? formatSize(change.newSize) : "-"
+        }`}
+      >
+        <span style={styles.icon}>{marker.sign}</span>
+        {parts[parts.length - 1]}
+        <span style={styles.badge}>{formatDelta(change.sizeDelta)}</span>
+      </div>
+    );
+  }
+
+  return (
+    <div>
+      <div style={styles.diffSummary}>
+        {diff.from} → {diff.to}: <span style={styles.diffAdded}>{diff.added} added</span>,{" "}
+        <span style={styles.diffRemoved}>{diff.removed} removed</span>,{" "}
+        <span style={styles.warning}>{diff.modified} changed</span>, {diff.unchanged} unchanged (
+        {formatDelta(diff.sizeDelta)}){" "}
+        <button style={{ ...styles.button, ...styles.buttonSecondary }} onClick={onClose}>
+          Close
+        </button>
+      </div>
+      {rows.length > 0 ? rows : <div style={styles.diffSummary}>The versions are identical.</div>}
+    </div>
+  );
+}
+
+// Helper function to format the line numbers of a diff line
+function lineNumber(number: number | undefined): string {
+  return (number === undefined ? "" : String(number)).padStart(5) + " ";
+}
+
+// FileDiffView component renders the line diff of one file
+function FileDiffView({
+  fileDiff,
+  layout,
+  onToggleLayout,
+  onClose,
+}: {
+  fileDiff: FileDiff;
+  layout: DiffLayout;
+  onToggleLayout: () => void;
+  onClose: () => void;
+}) {
+  const [showLines, setShowLines] = useState(false);
+  const colors: Record<string, React.CSSProperties> = {
+    added: styles.diffAdded,
+    removed: styles.diffRemoved,
+    changed: styles.warning,
+    modified: styles.warning,
+  };
+  // Config files that parse on both sides are shown key by key
+  const config = showLines ? undefined : fileDiff.config;
+  const name = (fileDiff.newPath ?? fileDiff.oldPath ?? "").split(/[\\/]/).pop();
+
+  let body: React.ReactNode;
+  if (fileDiff.kind !== "text") {
+    body = (
+      <div style={styles.diffSummary}>
+        {fileDiff.kind === "binary" ? "Binary file" : "File too large for a line diff"}:{" "}
+        {fileDiff.identical
+          ? "contents are identical."
+          : `${formatSize(fileDiff.oldSize)} → ${formatSize(fileDiff.newSize)}.`}
+      </div>
+    );
+  } else if (fileDiff.identical) {
+    body = <div style={styles.diffSummary}>The files are identical.</div>;
+  } else if (config) {
+    body =
+      config.changes.length === 0 ? (
+        <div style={styles.diffSummary}>No key changes; only formatting or key order differs.</div>
+      ) : (
+        config.changes.map((change) => (
+          <div key={change.path} style={{ ...styles.diffCode, ...colors[change.change] }}>
+            {change.change === "added" ? "+" : change.change === "removed" ? "-" : "~"} {change.path}
+            {change.oldValue !== undefined && `  ${change.oldValue}`}
+            {change.change === "modified" && " →"}
+            {change.newValue !== undefined && `  ${change.newValue}`}
+          </div>
+        ))
+      );
+  } else {
+    body = fileDiff.hunks.map((hunk, i) => (
+      <div key={i}>
+        <div style={styles.diffHunkHeader}>
+          @@ -{hunk.oldStart},{hunk.oldLines} +{hunk.newStart},{hunk.newLines} @@ {hunk.header ?? ""}
+        </div>
+        {layout === "unified"
+          ? (hunk.lines ?? []).map((line, j) => (
+              <div key={j} style={{ ...styles.diffCode, ...colors[line.kind] }}>
+                {lineNumber(line.oldLine)}
+                {lineNumber(line.newLine)}
+                {line.kind === "added" ? "+" : line.kind === "removed" ? "-" : " "} {line.text}
+              </div>
+            ))
+          : (hunk.rows ?? []).map((row, j) => (
+              <div key={j} style={{ display: "flex", ...colors[row.kind] }}>
+                <div style={{ ...styles.diffCode, ...styles.diffSide }}>
+                  {row.left && `${lineNumber(row.left.number)}${row.left.text}`}
+                </div>
+                <div style={{ ...styles.diffCode, ...styles.diffSide }}>
+                  {row.right && `${lineNumber(row.right.number)}${row.right.text}`}
+                </div>
+              </div>
+            ))}
+      </div>
+    ));
+  }
+
+  return (
+    <div>
+      <div style={styles.diffSummary}>
+        {config ? (
+          <>
+            {name} ({config.format} keys): <span style={styles.diffAdded}>+{config.added}</span>{" "}
+            <span style={styles.diffRemoved}>-{config.removed}</span>{" "}
+            <span style={styles.warning}>~{config.modified}</span>
+          </>
+        ) : (
+          <>
+            {name}: <span style={styles.diffAdded}>+{fileDiff.added}</span>{" "}
+            <span style={styles.diffRemoved}>-{fileDiff.removed}</span>
+            {fileDiff.approximate && " (approximate)"}
+          </>
+        )}{" "}
+        {fileDiff.config && !fileDiff.identical && (
+          <button
+            style={{ ...styles.button, ...styles.buttonSecondary }}
+            onClick={() => setShowLines(!showLines)}
+          >
+            {showLines ? "Keys" : "Lines"}
+          </button>
+        )}{" "}
+        {fileDiff.kind === "text" && !config && (
+          <button style={{ ...styles.button, ...styles.buttonSecondary }} onClick={onToggleLayout}>
+            {layout === "unified" ? "Side by side" : "Unified"}
+          </button>
+        )}{" "}
+        <button style={{ ...styles.button, ...styles.buttonSecondary }} onClick={onClose}>
+          Back
+        </button>
+      </div>
+      {body}
+    </div>
+  );
+}
+
+// DirectoryListing component renders one loaded directory level
+function DirectoryListing({
+  path,
+  level,
+  directories,
+  expandedNodes,
+  onToggle,
+  onOpenFile,
+  onLoadMore,
+}: {
+  path: string;
+  level: number;
+  directories: Record<string, TreePage>;
+  expandedNodes: Set<string>;
+  onToggle: (path: string) => void;
+  onOpenFile: (path: string) => void;
+  onLoadMore: (path: string) => void;
+}) {
+  const page = directories[path];
+
+  // Listings are fetched on first render and again after a rescan clears them
+  useEffect(() => {
+    if (!page) onLoadMore(path);
+  }, [page, path]);
+
+  if (!page) {
+    return (
+      <div style={{ ...styles.treeItem, paddingLeft: `${level * 20 + 8}px` }}>
+        Loading...
+      </div>
+    );
+  }
+
+  return (
+    <div>
+      {page.entries.map((entry) => (
+        <ModuleTreeNode
+          key={entry.path}
+          node={entry}
+          level={level}
+          directories={directories}
+          expandedNodes={expandedNodes}
+          onToggle={onToggle}
+          onOpenFile={onOpenFile}
+          onLoadMore={onLoadMore}
+        />
+      ))}
+      {page.nextCursor !== null && (
+        <div
+          style={{ ...styles.treeItem, paddingLeft: `${level * 20 + 8}px` }}
+          onClick={() => onLoadMore(path)}
+        >
+          Load more ({page.total - page.entries.length} remaining)
+        </div>
+      )}
+    </div>
+  );
+}
+
 // ModuleTreeNode component
 function ModuleTreeNode({
   node,
   level,
+  directories,
   expandedNodes,
   onToggle,
   onOpenFile,
+  onLoadMore,
 }: {
-  node: FileNode;
+  node: TreeEntry;
   level: number;
+  directories: Record<string, TreePage>;
   expandedNodes: Set<string>;
   onToggle: (path: string) => void;
   onOpenFile: (path: string) => void;
+  onLoadMore: (path: string) => void;
 }) {
   const [hovered, setHovered] = useState(false);
   const isExpanded = expandedNodes.has(node.path);
@@ -326,20 +829,22 @@ function ModuleTreeNode({
         )}
         {!node.isDirectory && <span style={styles.icon}>📄</span>}
         <span>{node.name}</span>
+        <span style={{ marginLeft: "auto", color: "#858585", fontSize: "12px" }}>
+          {node.isDirectory
+            ? node.childCount !== null && `${node.childCount} items`
+            : formatSize(node.size)}
+        </span>
       </div>
-      {node.isDirectory && isExpanded && node.children && (
-        <div>
-          {node.children.map((child) => (
-            <ModuleTreeNode
-              key={child.path}
-              node={child}
-              level={level + 1}
-              expandedNodes={expandedNodes}
-              onToggle={onToggle}
-              onOpenFile={onOpenFile}
-            />
-          ))}
-        </div>
+      {node.isDirectory && isExpanded && (
+        <DirectoryListing
+          path={node.path}
+          level={level + 1}
+          directories={directories}
+          expandedNodes={expandedNodes}
+          onToggle={onToggle}
+          onOpenFile={onOpenFile}
+          onLoadMore={onLoadMore}
+        />
       )}
     </div>
   );
@@ -347,40 +852,121 @@ function ModuleTreeNode({
 
 // ModuleViewColumn component
 function ModuleViewColumn({
-  modules,
+  versionPath,
+  directories,
   expandedNodes,
   onToggle,
   onOpenFile,
+  onLoadMore,
   onDeleteVersion,
+  onRepairVersion,
+  sealed,
+  onToggleSeal,
+  onCloneVersion,
+  onMoveVersion,
+  diff,
+  onCompareVersion,
+  onCloseDiff,
+  onSelectChange,
+  fileDiff,
+  diffLayout,
+  onToggleDiffLayout,
+  onCloseFileDiff,
 }: {
-  modules: FileNode[];
+  versionPath: string | null;
+  directories: Record<string, TreePage>;
   expandedNodes: Set<string>;
   onToggle: (path: string) => void;
   onOpenFile: (path: string) => void;
+  onLoadMore: (path: string) => void;
   onDeleteVersion: () => void;
+  onRepairVersion: (() => void) | null;
+  sealed: boolean;
+  onToggleSeal: () => void;
+  onCloneVersion: () => void;
+  onMoveVersion: () => void;
+  diff: VersionDiff | null;
+  onCompareVersion: () => void;
+  onCloseDiff: () => void;
+  onSelectChange: (change: FileChange) => void;
+  fileDiff: FileDiff | null;
+  diffLayout: DiffLayout;
+  onToggleDiffLayout: () => void;
+  onCloseFileDiff: () => void;
 }) {
   return (
     <div style={{ ...styles.column, ...styles.moduleColumn }}>
       <div style={styles.header}>
         <span>Module View</span>
-        <button
-          style={{ ...styles.button, ...styles.buttonSecondary }}
-          onClick={onDeleteVersion}
-        >
-          Delete Version
-        </button>
+        <div style={styles.headerActions}>
+          {onRepairVersion && (
+            <button style={styles.button} onClick={onRepairVersion}>
+              Repair
+            </button>
+          )}
+          {versionPath && (
+            <button
+              style={{ ...styles.button, ...styles.buttonSecondary }}
+              onClick={onCloneVersion}
+            >
+              Clone
+            </button>
+          )}
+          {versionPath && (
+            <button
+              style={{ ...styles.button, ...styles.buttonSecondary }}
+              onClick={onMoveVersion}
+            >
+              Move
+            </button>
+          )}
+          {versionPath && (
+            <button
+              style={{ ...styles.button, ...styles.buttonSecondary }}
+              onClick={onCompareVersion}
+            >
+              Compare
+            </button>
+          )}
+          {versionPath && (
+            <button
+              style={{ ...styles.button, ...styles.buttonSecondary }}
+              onClick={onToggleSeal}
+            >
+              {sealed ? "Unseal" : "Seal"}
+            </button>
+          )}
+          <button
+            style={{ ...styles.button, ...styles.buttonSecondary }}
+            onClick={onDeleteVersion}
+          >
+            Delete Version
+          </button>
+        </div>
       </div>
       <div style={styles.list}>
-        {modules.map((node) => (
-          <ModuleTreeNode
-            key={node.path}
-            node={node}
+        {fileDiff && (
+          <FileDiffView
+            fileDiff={fileDiff}
+            layout={diffLayout}
+            onToggleLayout={onToggleDiffLayout}
+            onClose={onCloseFileDiff}
+          />
+        )}
+        {diff && !fileDiff && (
+          <VersionDiffView diff={diff} onClose={onCloseDiff} onSelectChange={onSelectChange} />
+        )}
+        {versionPath && !diff && (
+          <DirectoryListing
+            path={versionPath}
             level={0}
+            directories={directories}
             expandedNodes={expandedNodes}
             onToggle={onToggle}
             onOpenFile={onOpenFile}
+            onLoadMore={onLoadMore}
           />
-        ))}
+        )}
       </div>
     </div>
   );
@@ -398,6 +984,8 @@ function SettingsPanel({
 }) {
   const [localPath, setLocalPath] = useState(config.localPath);
   const [mirrorPath, setMirrorPath] = useState(config.mirrorPath);
+  const [trashRetentionDays, setTrashRetentionDays] = useState(config.trashRetentionDays);
+  const [scrubIntervalHours, setScrubIntervalHours] = useState(config.scrubIntervalHours);
 
   return (
     <>
@@ -422,6 +1010,26 @@ function SettingsPanel({
             style={styles.input}
           />
         </label>
+        <label style={styles.label}>
+          Keep Trash For (days, 0 = forever):
+          <input
+            type="number"
+            min={0}
+            value={trashRetentionDays}
+            onChange={(e) => setTrashRetentionDays(Math.max(0, Number(e.target.value) || 0))}
+            style={styles.input}
+          />
+        </label>
+        <label style={styles.label}>
+          Integrity Check Interval (hours, 0 = off):
+          <input
+            type="number"
+            min={0}
+            value={scrubIntervalHours}
+            onChange={(e) => setScrubIntervalHours(Math.max(0, Number(e.target.value) || 0))}
+            style={styles.input}
+          />
+        </label>
         <div style={styles.buttonGroup}>
           <button
             style={{ ...styles.button, ...styles.buttonSecondary }}
@@ -431,7 +1039,10 @@ function SettingsPanel({
           </button>
           <button
             style={styles.button}
-            onClick={() => onSave({ localPath, mirrorPath })}
+            onClick={() =>
+              // Settings not shown here are kept as they are
+              onSave({ ...config, localPath, mirrorPath, trashRetentionDays, scrubIntervalHours })
+            }
           >
             Save
           </button>
@@ -448,17 +1059,55 @@ export function App() {
     selectedProgram: null,
     selectedVersion: null,
     expandedNodes: new Set(),
+    directories: {},
     config: {
       localPath: "C:\\ProgramManager",
       mirrorPath: "",
+      trashRetentionDays: 30,
+      scrubIntervalHours: 24 * 7,
     },
     showSettings: false,
+    ingest: null,
+    diff: null,
+    fileDiff: null,
+    diffLayout: "unified",
   });
 
   // Load config and programs on mount
   useEffect(() => {
     loadConfig();
-    loadPrograms();
+    loadPrograms(false);
+
+    const unlisteners = [
+      listen<Program[]>("catalog-index-updated", (event) => {
+        setState((prev) => ({ ...prev, programs: event.payload, directories: {} }));
+      }),
+      // The watcher has already synced the index, so a cached load is enough
+      ...[
+        "catalog-program-added",
+        "catalog-program-removed",
+        "catalog-version-added",
+        "catalog-version-removed",
+      ].map((name) => listen(name, () => loadPrograms(false))),
+      listen("scrub-finished", () => loadPrograms(false)),
+      listen<DropPlan>("file-drop-received", (event) => handleDrop(event.payload)),
+      listen<IngestProgress>("drop-ingest-progress", (event) => {
+        setState((prev) => ({ ...prev, ingest: event.payload }));
+      }),
+      listen<{ path: string }>("catalog-file-changed", (event) => {
+        // Drop the cached listing of the containing directory so it reloads
+        const path = event.payload.path;
+        const parent = path.substring(0, Math.max(path.lastIndexOf("/"), path.lastIndexOf("\\")));
+        setState((prev) => {
+          if (!prev.directories[parent]) return prev;
+          const { [parent]: _stale, ...directories } = prev.directories;
+          return { ...prev, directories };
+        });
+      }),
+    ];
+    return () => {
+      unlisteners.forEach((unlisten) => unlisten.then((fn) => fn()));
+    };
   }, []);
 
   async function loadConfig() {
@@ -466,8 +1115,9 @@ export function App() {
       const appData = await appDataDir();
       const configPath = await join(appData, "config.json");
       const configText = await readTextFile(configPath);
-      const config = JSON.parse(configText);
-      setState((prev) => ({ ...prev, config }));
+      // Fields missing from an older config file keep their defaults
+      const saved = JSON.parse(configText);
+      setState((prev) => ({ ...prev, config: { ...prev.config, ...saved } }));
     } catch (e) {
       // Use default config if file doesn't exist
       console.log("Using default config");
@@ -480,48 +1130,21 @@ export function App() {
       const configPath = await join(appData, "config.json");
       await writeTextFile(configPath, JSON.stringify(config, null, 2));
       setState((prev) => ({ ...prev, config, showSettings: false }));
+      // The storage root may have moved
+      await invoke("restart_watcher");
+      await loadPrograms();
     } catch (e) {
       console.error("Error saving config:", e);
     }
   }
 
-  async function loadPrograms() {
+  async function loadPrograms(refresh: boolean = true) {
     try {
-      const localPath = state.config.localPath;
-      // Create directory if it doesn't exist
-      try {
-        await createDir(localPath, { recursive: true });
-      } catch (e) {
-        // Directory might already exist
-      }
-
-      const entries = await readDir(localPath);
-      const programs: Program[] = [];
-
-      for (const entry of entries) {
-        if (entry.children) {
-          const versions: ProgramVersion[] = [];
-          const versionEntries = await readDir(entry.path);
-
-          for (const versionEntry of versionEntries) {
-            if (versionEntry.children) {
-              const modules = await readDirRecursive(versionEntry.path);
-              versions.push({
-                version: versionEntry.name || "",
-                path: versionEntry.path,
-                modules,
-              });
-            }
-          }
-
-          programs.push({
-            name: entry.name || "",
-            versions,
-          });
-        }
-      }
-
-      setState((prev) => ({ ...prev, programs }));
+      // Without refresh the indexed catalog comes back immediately and any
+      // changes arrive later through "catalog-index-updated"
+      const programs = await invoke<Program[]>("load_catalog", { refresh });
+      // Directory listings may be stale after a rescan
+      setState((prev) => ({ ...prev, programs, directories: {} }));
     } catch (e) {
       console.error("Error loading programs:", e);
     }
@@ -532,11 +1155,33 @@ export function App() {
       ...prev,
       selectedProgram: name,
       selectedVersion: null,
+      diff: null,
+      fileDiff: null,
     }));
+    // Files dropped on the window become versions of this program
+    invoke("set_drop_target", { program: name }).catch((e) =>
+      console.error("Error setting drop target:", e)
+    );
+  }
+
+  async function loadDirectory(path: string, cursor: number = 0) {
+    try {
+      const page = await invoke<TreePage>("list_directory", { path, cursor });
+      setState((prev) => {
+        const existing = prev.directories[path];
+        const merged =
+          cursor > 0 && existing
+            ? { ...page, entries: [...existing.entries, ...page.entries] }
+            : page;
+        return { ...prev, directories: { ...prev.directories, [path]: merged } };
+      });
+    } catch (e) {
+      console.error("Error listing directory:", e);
+    }
   }
 
   function handleSelectVersion(version: string) {
-    setState((prev) => ({ ...prev, selectedVersion: version }));
+    setState((prev) => ({ ...prev, selectedVersion: version, diff: null, fileDiff: null }));
   }
 
   function handleToggleNode(path: string) {
@@ -551,6 +1196,15 @@ export function App() {
     });
   }
 
+  function handleLoadMore(path: string) {
+    const page = state.directories[path];
+    if (!page) {
+      loadDirectory(path);
+    } else if (page.nextCursor !== null) {
+      loadDirectory(path, page.nextCursor);
+    }
+  }
+
   async function handleOpenFile(path: string) {
     try {
       await open(path);
@@ -563,11 +1217,13 @@ export function App() {
     const name = prompt("Enter program name:");
     if (name) {
       try {
-        const programPath = await join(state.config.localPath, name);
-        await createDir(programPath, { recursive: true });
+        // The backend validates the name, so it cannot reach outside the root
+        await invoke("create_program", { name });
+        handleSelectProgram(name.normalize("NFC"));
         await loadPrograms();
       } catch (e) {
         console.error("Error creating program:", e);
+        alert(formatError(e));
       }
     }
   }
@@ -578,38 +1234,252 @@ export function App() {
     const version = prompt("Enter version number:");
     if (version) {
       try {
-        const versionPath = await join(
-          state.config.localPath,
-          state.selectedProgram,
-          version
-        );
-        await createDir(versionPath, { recursive: true });
+        await invoke("create_version", { program: state.selectedProgram, version });
+        setState((prev) => ({ ...prev, selectedVersion: version.normalize("NFC") }));
         await loadPrograms();
       } catch (e) {
         console.error("Error creating version:", e);
+        alert(formatError(e));
+      }
+    }
+  }
+
+  async function handleDrop(plan: DropPlan) {
+    if (plan.rejected.length > 0) {
+      alert(
+        "Only folders and .zip, .tar.gz or .tar.zst archives can be added:\n" +
+          plan.rejected.join("\n")
+      );
+    }
+    if (plan.candidates.length === 0) return;
+    if (!plan.program) {
+      alert("Select a program to add the dropped versions to.");
+      return;
+    }
+
+    for (const candidate of plan.candidates) {
+      const version = prompt(
+        `Version name for ${candidate.path}:`,
+        candidate.suggestedVersion
+      );
+      if (!version) continue;
+      try {
+        // The version only appears once the copy or extraction is complete
+        await invoke("ingest_dropped", {
+          program: plan.program,
+          source: candidate.path,
+          version,
+        });
+      } catch (e) {
+        console.error("Error adding dropped version:", e);
+        alert(formatError(e));
+      } finally {
+        setState((prev) => ({ ...prev, ingest: null }));
       }
     }
+    await loadPrograms(false);
   }
 
   async function handleDeleteVersion() {
     if (!state.selectedProgram || !state.selectedVersion) return;
 
-    if (confirm(`Delete version ${state.selectedVersion}?`)) {
+    if (confirm(`Move version ${state.selectedVersion} to the trash?`)) {
       try {
-        const program = state.programs.find((p) => p.name === state.selectedProgram);
-        const version = program?.versions.find((v) => v.version === state.selectedVersion);
-        
-        if (version) {
-          await invoke("remove_dir_all", { path: version.path });
-          setState((prev) => ({ ...prev, selectedVersion: null }));
-          await loadPrograms();
-        }
+        await invoke("trash_version", {
+          program: state.selectedProgram,
+          version: state.selectedVersion,
+        });
+        setState((prev) => ({ ...prev, selectedVersion: null }));
+        await loadPrograms();
       } catch (e) {
         console.error("Error deleting version:", e);
+        alert(formatError(e));
+      }
+    }
+  }
+
+  async function handleRepairVersion() {
+    if (!state.selectedProgram || !state.selectedVersion) return;
+
+    if (confirm(`Repair version ${state.selectedVersion} from its healthy copy?`)) {
+      try {
+        const report = await invoke<{ location: string; repaired: string[]; intact: boolean }>(
+          "repair_version",
+          { program: state.selectedProgram, version: state.selectedVersion }
+        );
+        alert(
+          `Repaired ${report.repaired.length} file(s) on the ${report.location} copy.` +
+            (report.intact ? "" : " The version still does not match its manifest.")
+        );
+        await loadPrograms(false);
+      } catch (e) {
+        console.error("Error repairing version:", e);
+        alert(formatError(e));
       }
     }
   }
 
+  async function handleToggleSeal(sealed: boolean) {
+    if (!state.selectedProgram || !state.selectedVersion) return;
+
+    try {
+      if (sealed) {
+        const reason = prompt(`Reason for unsealing ${state.selectedVersion}:`);
+        if (reason === null) return;
+        await invoke("unseal_version", {
+          program: state.selectedProgram,
+          version: state.selectedVersion,
+          reason,
+        });
+      } else {
+        if (!confirm(`Seal version ${state.selectedVersion}? Its files will become read-only.`)) {
+          return;
+        }
+        await invoke("seal_version", {
+          program: state.selectedProgram,
+          version: state.selectedVersion,
+        });
+      }
+      await loadPrograms(false);
+    } catch (e) {
+      console.error("Error changing seal:", e);
+      alert(formatError(e));
+    }
+  }
+
+  async function handleCloneVersion() {
+    if (!state.selectedProgram || !state.selectedVersion) return;
+
+    const version = prompt(
+      `Clone ${state.selectedVersion} as:`,
+      nextVersionName(state.selectedVersion)
+    );
+    if (!version) return;
+    try {
+      // Reflinks share unchanged data where the filesystem allows it and
+      // fall back to plain copies elsewhere
+      await invoke("clone_version", {
+        program: state.selectedProgram,
+        parent: state.selectedVersion,
+        version,
+        mode: "reflink",
+      });
+      setState((prev) => ({ ...prev, selectedVersion: version }));
+      await loadPrograms(false);
+    } catch (e) {
+      console.error("Error cloning version:", e);
+      alert(formatError(e));
+    }
+  }
+
+  async function handleRenameProgram() {
+    if (!state.selectedProgram) return;
+
+    const newName = prompt(`Rename ${state.selectedProgram} to:`, state.selectedProgram);
+    if (!newName || newName === state.selectedProgram) return;
+    try {
+      // The mirror copy, sync state and version labels move along with it
+      await invoke("rename_program", { program: state.selectedProgram, newName });
+      handleSelectProgram(newName);
+      await loadPrograms(false);
+    } catch (e) {
+      console.error("Error renaming program:", e);
+      alert(formatError(e));
+    }
+  }
+
+  async function handleMoveVersion() {
+    if (!state.selectedProgram || !state.selectedVersion) return;
+
+    const target = prompt(
+      `Move ${state.selectedProgram}/${state.selectedVersion} to (program/version):`,
+      `${state.selectedProgram}/${state.selectedVersion}`
+    );
+    if (!target) return;
+    const slash = target.lastIndexOf("/");
+    const toProgram = slash >= 0 ? target.slice(0, slash) : state.selectedProgram;
+    const toVersion = target.slice(slash + 1);
+    if (toProgram === state.selectedProgram && toVersion === state.selectedVersion) return;
+    try {
+      await invoke("move_version", {
+        program: state.selectedProgram,
+        version: state.selectedVersion,
+        toProgram,
+        toVersion,
+      });
+      if (toProgram !== state.selectedProgram) handleSelectProgram(toProgram);
+      setState((prev) => ({ ...prev, selectedVersion: toVersion }));
+      await loadPrograms(false);
+    } catch (e) {
+      console.error("Error moving version:", e);
+      alert(formatError(e));
+    }
+  }
+
+  async function handleCompareVersion() {
+    if (!state.selectedProgram || !state.selectedVersion) return;
+
+    // Versions are sorted oldest first, so suggest the one before this
+    const versions = state.programs.find((p) => p.name === state.selectedProgram)?.versions ?? [];
+    const index = versions.findIndex((v) => v.version === state.selectedVersion);
+    const from = prompt(
+      `Compare ${state.selectedVersion} with version:`,
+      versions[index - 1]?.version ?? ""
+    );
+    if (!from) return;
+    try {
+      const diff = await invoke<VersionDiff>("diff_versions", {
+        program: state.selectedProgram,
+        from,
+        to: state.selectedVersion,
+      });
+      setState((prev) => ({ ...prev, diff }));
+    } catch (e) {
+      console.error("Error comparing versions:", e);
+      alert(formatError(e));
+    }
+  }
+
+  async function loadFileDiff(
+    oldPath: string | undefined,
+    newPath: string | undefined,
+    layout: DiffLayout
+  ) {
+    try {
+      const fileDiff = await invoke<FileDiff>("diff_file", { oldPath, newPath, layout });
+      setState((prev) => ({ ...prev, fileDiff, diffLayout: layout }));
+    } catch (e) {
+      console.error("Error comparing files:", e);
+      alert(formatError(e));
+    }
+  }
+
+  function handleSelectChange(change: FileChange) {
+    const diff = state.diff;
+    if (!diff) return;
+
+    const versions = state.programs.find((p) => p.name === diff.program)?.versions ?? [];
+    const dirOf = (version: string) => versions.find((v) => v.version === version)?.path;
+    const fromDir = dirOf(diff.from);
+    const toDir = dirOf(diff.to);
+    // An added or removed file is compared with nothing
+    loadFileDiff(
+      change.change !== "added" && fromDir ? `${fromDir}/${change.path}` : undefined,
+      change.change !== "removed" && toDir ? `${toDir}/${change.path}` : undefined,
+      state.diffLayout
+    );
+  }
+
+  function handleToggleDiffLayout() {
+    const fileDiff = state.fileDiff;
+    if (!fileDiff) return;
+    loadFileDiff(
+      fileDiff.oldPath,
+      fileDiff.newPath,
+      state.diffLayout === "unified" ? "sideBySide" : "unified"
+    );
+  }
+
   const selectedProgramData = state.programs.find(
     (p) => p.name === state.selectedProgram
   );
@@ -624,20 +1494,39 @@ export function App() {
         selectedProgram={state.selectedProgram}
         onSelectProgram={handleSelectProgram}
         onAddProgram={handleAddProgram}
+        onRenameProgram={handleRenameProgram}
       />
       <VersionsColumn
         versions={selectedProgramData?.versions || []}
+        latest={selectedProgramData?.latest}
+        latestStable={selectedProgramData?.latestStable}
         selectedVersion={state.selectedVersion}
         onSelectVersion={handleSelectVersion}
         onAddVersion={handleAddVersion}
         programName={state.selectedProgram}
+        ingest={state.ingest}
       />
       <ModuleViewColumn
-        modules={selectedVersionData?.modules || []}
+        versionPath={selectedVersionData?.path || null}
+        directories={state.directories}
         expandedNodes={state.expandedNodes}
         onToggle={handleToggleNode}
         onOpenFile={handleOpenFile}
+        onLoadMore={handleLoadMore}
         onDeleteVersion={handleDeleteVersion}
+        onRepairVersion={isCorrupted(selectedVersionData) ? handleRepairVersion : null}
+        sealed={selectedVersionData?.sealed ?? false}
+        onToggleSeal={() => handleToggleSeal(selectedVersionData?.sealed ?? false)}
+        onCloneVersion={handleCloneVersion}
+        onMoveVersion={handleMoveVersion}
+        diff={state.diff}
+        onCompareVersion={handleCompareVersion}
+        onCloseDiff={() => setState((prev) => ({ ...prev, diff: null }))}
+        onSelectChange={handleSelectChange}
+        fileDiff={state.fileDiff}
+        diffLayout={state.diffLayout}
+        onToggleDiffLayout={handleToggleDiffLayout}
+        onCloseFileDiff={() => setState((prev) => ({ ...prev, fileDiff: null }))}
       />
       {state.showSettings && (
         <SettingsPanel